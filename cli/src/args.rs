@@ -1,20 +1,107 @@
-use clap::{Arg, ArgAction, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use compiler::{Arch, Target};
+use lexer::Extensions;
+use parser::cell::CellWidth;
+use parser::eof::EofPolicy;
+use parser::limits::Limits;
+use parser::tape::{Overflow, TapePolicy};
+use std::num::NonZeroUsize;
+use std::time::Duration;
 
 #[derive(Debug)]
 pub enum RunMode {
     Assemble(String, Option<String>),
     Repl,
     Compile(String, Option<String>),
+    Run(String, Limits),
+    Debug(String, Limits),
 }
 
-pub struct Args;
+#[derive(Debug)]
+pub struct Args {
+    pub mode: RunMode,
+    pub cell_width: CellWidth,
+    pub eof_policy: EofPolicy,
+    pub tape_policy: TapePolicy,
+    pub extensions: Extensions,
+    pub target: Target,
+    pub arch: Arch,
+}
+
+fn parse_cell_width(value: &str) -> Result<CellWidth, String> {
+    let bits = value.parse::<u32>().map_err(|e| e.to_string())?;
+    CellWidth::try_from(bits).map_err(|e| e.to_string())
+}
+
+fn parse_overflow(value: &str) -> Result<Overflow, String> {
+    Overflow::try_from(value).map_err(|e| e.to_string())
+}
+
+fn parse_tape_len(value: &str) -> Result<NonZeroUsize, String> {
+    let cells = value.parse::<usize>().map_err(|e| e.to_string())?;
+    NonZeroUsize::new(cells).ok_or("the tape must have at least 1 cell".to_string())
+}
+
+fn parse_timeout(value: &str) -> Result<Duration, String> {
+    let seconds = value.parse::<f64>().map_err(|e| e.to_string())?;
+    Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())
+}
+
+fn parse_eof_policy(value: &str) -> Result<EofPolicy, String> {
+    EofPolicy::try_from(value).map_err(|e| e.to_string())
+}
+
+fn parse_target(value: &str) -> Result<Target, String> {
+    Target::try_from(value).map_err(|e| e.to_string())
+}
+
+fn parse_triple(value: &str) -> Result<Arch, String> {
+    Arch::try_from(value).map_err(|e| e.to_string())
+}
+
+/// the arguments of the subcommands that interpret a program, which can limit
+/// how long it runs for
+fn limit_args() -> [Arg; 2] {
+    [
+        Arg::new("fuel")
+            .long("fuel")
+            .help("Stop after running this many operations")
+            .value_name("STEPS")
+            .value_parser(clap::value_parser!(u64)),
+        Arg::new("timeout")
+            .long("timeout")
+            .help("Stop after running for this many seconds")
+            .value_name("SECONDS")
+            .value_parser(parse_timeout),
+    ]
+}
+
+fn limits(matches: &ArgMatches) -> Limits {
+    Limits {
+        fuel: matches.get_one::<u64>("fuel").copied(),
+        timeout: matches.get_one::<Duration>("timeout").copied(),
+    }
+}
 
 impl Args {
-    pub fn parse() -> RunMode {
+    pub fn parse() -> Args {
         let matches = Command::new("bfck")
             .version("0.1.0")
             .about("Interpreter and compiler for brainfuck")
             .after_help("When no options are passed, a REPL is initialized instead")
+            .args_conflicts_with_subcommands(true)
+            .subcommand(
+                Command::new("run")
+                    .about("Interpret a brainfuck file without compiling it")
+                    .arg(Arg::new("file").help("Source file to run").required(true))
+                    .args(limit_args()),
+            )
+            .subcommand(
+                Command::new("debug")
+                    .about("Step through a brainfuck file interactively")
+                    .arg(Arg::new("file").help("Source file to debug").required(true))
+                    .args(limit_args()),
+            )
             .arg(
                 Arg::new("assemble")
                     .short('s')
@@ -30,8 +117,124 @@ impl Args {
                     .required(false),
             )
             .arg(Arg::new("output").help("Output file name").required(false))
+            .arg(
+                Arg::new("target")
+                    .long("target")
+                    .help("What to compile into, either native, c, wat or llvm, all but x86_64 native need --assemble")
+                    .value_name("TARGET")
+                    .value_parser(parse_target)
+                    .default_value("native"),
+            )
+            .arg(
+                Arg::new("triple")
+                    .long("triple")
+                    .help("Target triple of native code, for x86_64, aarch64 or riscv64 linux")
+                    .value_name("TRIPLE")
+                    .value_parser(parse_triple)
+                    .default_value("x86_64-linux"),
+            )
+            .arg(
+                Arg::new("cell-width")
+                    .long("cell-width")
+                    .help("Width of each cell on the tape in bits, either 8, 16 or 32")
+                    .value_name("BITS")
+                    .value_parser(parse_cell_width)
+                    .default_value("8")
+                    .global(true),
+            )
+            .arg(
+                Arg::new("eof")
+                    .long("eof")
+                    .help("What reading does to the cell when there is no more input")
+                    .value_name("POLICY")
+                    .value_parser(parse_eof_policy)
+                    .default_value("unchanged")
+                    .global(true),
+            )
+            .arg(
+                Arg::new("tape-len")
+                    .long("tape-len")
+                    .help("Maximum amount of cells on the tape")
+                    .value_name("CELLS")
+                    .value_parser(parse_tape_len)
+                    .global(true),
+            )
+            .arg(
+                Arg::new("tape-overflow")
+                    .long("tape-overflow")
+                    .help("What moving past the end of the tape does, either grow, wrap or error")
+                    .value_name("MODE")
+                    .value_parser(parse_overflow)
+                    .default_value("grow")
+                    .global(true),
+            )
+            .arg(
+                Arg::new("no-left")
+                    .long("no-left")
+                    .help("Make moving left of the starting cell an error")
+                    .action(ArgAction::SetTrue)
+                    .global(true),
+            )
+            .arg(
+                Arg::new("dump")
+                    .long("dump")
+                    .help("Treat `#` as a command that prints the cells around the pointer to stderr when interpreting")
+                    .action(ArgAction::SetTrue)
+                    .global(true),
+            )
             .get_matches();
 
+        let cell_width = *matches
+            .get_one::<CellWidth>("cell-width")
+            .expect("cell width has a default value");
+
+        let eof_policy = *matches
+            .get_one::<EofPolicy>("eof")
+            .expect("eof policy has a default value");
+
+        let tape_policy = TapePolicy {
+            max_len: matches.get_one::<NonZeroUsize>("tape-len").copied(),
+            allow_left: !matches.get_flag("no-left"),
+            overflow: *matches
+                .get_one::<Overflow>("tape-overflow")
+                .expect("tape overflow has a default value"),
+        };
+
+        let extensions = Extensions {
+            dump: matches.get_flag("dump"),
+        };
+
+        let target = *matches
+            .get_one::<Target>("target")
+            .expect("target has a default value");
+
+        let arch = *matches
+            .get_one::<Arch>("triple")
+            .expect("triple has a default value");
+
+        let mode = Args::run_mode(&matches);
+        Args {
+            mode,
+            cell_width,
+            eof_policy,
+            tape_policy,
+            extensions,
+            target,
+            arch,
+        }
+    }
+
+    fn run_mode(matches: &ArgMatches) -> RunMode {
+        if let Some(("run", matches)) = matches.subcommand() {
+            let file = matches.get_one::<String>("file").expect("file is required");
+            return RunMode::Run(file.clone(), limits(matches));
+        }
+
+        if let Some(("debug", matches)) = matches.subcommand() {
+            let file = matches.get_one::<String>("file").expect("file is required");
+            return RunMode::Debug(file.clone(), limits(matches));
+        }
+
         let assemble = matches.get_flag("assemble");
         let source = matches.get_one::<String>("source");
 
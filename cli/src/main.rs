@@ -1,9 +1,93 @@
 mod args;
 
+use interpreter::Interpreter;
+use parser::error::{Error, RuntimeError};
+
+/// everything that can make bfck fail, the kind of failure decides the exit
+/// code, so scripts can tell them apart, these are listed in the README
+#[derive(Debug)]
+enum Failure {
+    Interpreter(Error),
+    Compiler(compiler::Error),
+}
+
+impl Failure {
+    /// 1 for io errors and missing toolchains, 3 for invalid programs, 4 for
+    /// programs that failed while running, and 5 for programs that ran out of
+    /// fuel or time, 2 is what clap exits with on invalid arguments
+    fn exit_code(&self) -> i32 {
+        match self {
+            Failure::Interpreter(Error::Parse(_))
+            | Failure::Compiler(compiler::Error::Parse(_)) => 3,
+            Failure::Interpreter(Error::Runtime(e))
+            | Failure::Compiler(compiler::Error::Runtime(e)) => match e {
+                RuntimeError::OutOfFuel { .. } | RuntimeError::TimedOut { .. } => 5,
+                _ => 4,
+            },
+            _ => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Failure::Interpreter(e) => write!(f, "{}", e),
+            Failure::Compiler(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<Error> for Failure {
+    fn from(e: Error) -> Failure {
+        Failure::Interpreter(e)
+    }
+}
+
+impl From<compiler::Error> for Failure {
+    fn from(e: compiler::Error) -> Failure {
+        Failure::Compiler(e)
+    }
+}
+
 fn main() {
-    match args::Args::parse() {
-        args::RunMode::Repl => repl::run(),
-        args::RunMode::Assemble(source, output) => compiler::assemble(source, output),
-        args::RunMode::Compile(source, output) => compiler::compile(source, output),
+    let args = args::Args::parse();
+    let options = compiler::Options {
+        cell_width: args.cell_width,
+        eof_policy: args.eof_policy,
+        tape_policy: args.tape_policy,
+        target: args.target,
+        arch: args.arch,
+    };
+
+    let interpreter = Interpreter::new()
+        .with_cell_width(args.cell_width)
+        .with_eof_policy(args.eof_policy)
+        .with_tape_policy(args.tape_policy);
+
+    let result = match args.mode {
+        args::RunMode::Repl => {
+            repl::run(interpreter, args.extensions);
+            Ok(())
+        }
+        args::RunMode::Assemble(source, output) => {
+            compiler::assemble(&source, output, options).map_err(|e| (source, Failure::from(e)))
+        }
+        args::RunMode::Compile(source, output) => {
+            compiler::compile(&source, output, options).map_err(|e| (source, Failure::from(e)))
+        }
+        args::RunMode::Run(source, limits) => {
+            interpreter::run(&source, interpreter, limits, args.extensions)
+                .map_err(|e| (source, Failure::from(e)))
+        }
+        args::RunMode::Debug(source, limits) => {
+            interpreter::debug(&source, interpreter, limits, args.extensions)
+                .map_err(|e| (source, Failure::from(e)))
+        }
+    };
+
+    if let Err((source, e)) = result {
+        eprintln!("ERROR: {}: {}", source, e);
+        std::process::exit(e.exit_code());
     }
 }
@@ -0,0 +1,261 @@
+use parser::cell::CellWidth;
+use parser::eof::EofPolicy;
+
+use crate::gnu_as::Isa;
+
+use std::io::Write;
+
+/// `{tape_size}` must be replaced by the size of the tape in bytes
+///
+/// the data pointer lives in `x19`, which the kernel leaves alone on syscalls,
+/// like every register but `x0`
+static PRELUDE: &str = r#"    .global _start
+
+    .bss
+tape:
+    .skip {tape_size}
+input:
+    .skip 1
+
+    .section .rodata
+read_err_msg:
+    .ascii "failed to read from input\n"
+    .equ read_err_len, . - read_err_msg
+
+    .text
+_start:
+    adrp x19, tape
+    add x19, x19, :lo12:tape
+"#;
+
+static EXIT: &str = r#"    mov x0, #0
+    mov x8, #93
+    svc #0"#;
+
+/// `x1` must point to the cell to write
+static WRITE: &str = r#"_w:
+    mov x0, #1
+    mov x2, #1
+    mov x8, #64
+    svc #0
+    ret"#;
+
+/// the previous value of the cell is kept in `w3` for `{eof}`
+static READ: &str = r#"_rd:
+    mov w3, w0
+    mov x0, #0
+    adrp x1, input
+    add x1, x1, :lo12:input
+    mov x2, #1
+    mov x8, #63
+    svc #0
+    cmp x0, #0
+    b.lt _f
+    b.eq _rz
+    ldrb w0, [x1]
+    ret
+_rz:
+{eof}
+    ret
+_f:
+    mov x0, #2
+    adrp x1, read_err_msg
+    add x1, x1, :lo12:read_err_msg
+    mov x2, #read_err_len
+    mov x8, #64
+    svc #0
+    mov x0, #1
+    mov x8, #93
+    svc #0"#;
+
+/// AArch64 linux, cells are worked on in 32-bit registers, and storing them
+/// back keeps only as many bits as a cell has, which is how they wrap around
+///
+/// `cbz` and `cbnz` only reach 1 MiB away, `b` reaches 128 MiB away
+#[derive(Debug, Default)]
+pub struct Aarch64;
+
+impl Isa for Aarch64 {
+    const PRELUDE: &'static str = PRELUDE;
+    const WRITE: &'static str = WRITE;
+    const READ: &'static str = READ;
+    const EXIT: &'static str = EXIT;
+
+    const DATA_PTR: &'static str = "x19";
+    const CELL: &'static str = "w0";
+    const OTHER: &'static str = "w2";
+    const FACTOR: &'static str = "w1";
+    const WRITE_ARG: &'static str = "x1";
+    const ZERO: &'static str = "wzr";
+
+    fn eof(policy: EofPolicy) -> &'static str {
+        match policy {
+            EofPolicy::Unchanged => "    mov w0, w3",
+            EofPolicy::Zero => "    mov w0, #0",
+            EofPolicy::MinusOne => "    mov w0, #-1",
+        }
+    }
+
+    /// `mov` only takes 16-bit immediates, so bigger values are built 16 bits
+    /// at a time with `movz` and `movk`, a literal pool would only be in reach
+    /// of loads up to 1 MiB away
+    fn constant<W>(writer: &mut W, reg: &str, value: i64) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        if (-65536..=65535).contains(&value) {
+            return writeln!(writer, "    mov {}, #{}", reg, value);
+        }
+
+        let bits = match reg.starts_with('w') {
+            true => 32,
+            false => 64,
+        };
+        let value = value as u64 & (u64::MAX >> (64 - bits));
+        let mut op = "movz";
+        for shift in (0..bits).step_by(16) {
+            let chunk = (value >> shift) & 0xffff;
+            if chunk == 0 {
+                continue;
+            }
+            match shift {
+                0 => writeln!(writer, "    {} {}, #{}", op, reg, chunk)?,
+                shift => writeln!(writer, "    {} {}, #{}, lsl #{}", op, reg, chunk, shift)?,
+            }
+            op = "movk";
+        }
+        Ok(())
+    }
+
+    /// immediates of `add` and `sub` only have 12 bits, so bigger values go
+    /// through `w10` or `x10`, whichever is as wide as `dest`
+    fn add_immediate<W>(writer: &mut W, dest: &str, src: &str, value: i64) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        match value {
+            0 if dest == src => Ok(()),
+            0 => writeln!(writer, "    mov {}, {}", dest, src),
+            1..=4095 => writeln!(writer, "    add {}, {}, #{}", dest, src, value),
+            -4095..=-1 => writeln!(writer, "    sub {}, {}, #{}", dest, src, -value),
+            value => {
+                let scratch = match dest.starts_with('w') {
+                    true => "w10",
+                    false => "x10",
+                };
+                Aarch64::constant(writer, scratch, value)?;
+                writeln!(writer, "    add {}, {}, {}", dest, src, scratch)
+            }
+        }
+    }
+
+    /// cells away from the data pointer have their address put in `x9`
+    fn address<W>(writer: &mut W, offset: isize) -> std::io::Result<String>
+    where
+        W: Write,
+    {
+        if offset == 0 {
+            return Ok("[x19]".to_string());
+        }
+        Aarch64::add_immediate(writer, "x9", "x19", offset as i64)?;
+        Ok("[x9]".to_string())
+    }
+
+    fn load<W>(writer: &mut W, width: CellWidth, reg: &str, address: &str) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "    ldr{} {}, {}", suffix(width), reg, address)
+    }
+
+    fn store<W>(writer: &mut W, width: CellWidth, reg: &str, address: &str) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "    str{} {}, {}", suffix(width), reg, address)
+    }
+
+    fn branch_if_zero<W>(writer: &mut W, reg: &str, label: &str) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "    cbz {}, {}", reg, label)
+    }
+
+    fn branch_unless_zero<W>(writer: &mut W, reg: &str, label: &str) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "    cbnz {}, {}", reg, label)
+    }
+
+    fn jump<W>(writer: &mut W, label: &str) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "    b {}", label)
+    }
+
+    fn call<W>(writer: &mut W, label: &str) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "    bl {}", label)
+    }
+}
+
+/// the suffix of loads and stores as wide as a cell
+fn suffix(width: CellWidth) -> &'static str {
+    match width {
+        CellWidth::U8 => "b",
+        CellWidth::U16 => "h",
+        CellWidth::U32 => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_text;
+    use crate::gnu_as::GnuAs;
+    use crate::options::Options;
+
+    #[test]
+    fn loops_branch_over_jumps() {
+        let assembly = generate_text("+[.>]", |writer| {
+            GnuAs::<Aarch64>::new(writer, Options::default()).unwrap()
+        });
+        assert!(assembly.contains("    ldrb w0, [x19]\n    cbnz w0, _j0b\n    b _j0e\n_j0b:\n"));
+        assert!(assembly.contains("    ldrb w0, [x19]\n    cbz w0, _j0e\n    b _j0b\n_j0e:\n"));
+        assert!(assembly.contains("_w:\n"));
+        assert!(!assembly.contains("_rd:\n"));
+    }
+
+    #[test]
+    fn big_offsets_go_through_a_register() {
+        let options = Options {
+            cell_width: CellWidth::U32,
+            ..Default::default()
+        };
+        let assembly = generate_text(&format!("{}+", ">".repeat(2000)), |writer| {
+            GnuAs::<Aarch64>::new(writer, options).unwrap()
+        });
+        assert!(assembly.contains("    mov x10, #8000\n    add x19, x19, x10\n"));
+    }
+
+    #[test]
+    fn big_constants_are_built_16_bits_at_a_time() {
+        let options = Options {
+            cell_width: CellWidth::U32,
+            ..Default::default()
+        };
+        let assembly = generate_text(&format!("{}+", "<".repeat(20000)), |writer| {
+            GnuAs::<Aarch64>::new(writer, options).unwrap()
+        });
+        // -80000 as 64 bits is 0xfffffffffffec780
+        assert!(assembly.contains(
+            "    movz x10, #51072\n    movk x10, #65534, lsl #16\n    movk x10, #65535, lsl #32\n    movk x10, #65535, lsl #48\n"
+        ));
+        assert!(!assembly.contains("ldr x10, ="));
+    }
+}
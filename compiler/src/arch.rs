@@ -0,0 +1,60 @@
+/// the architecture native code is compiled for, every one of them is linux
+/// only, as compiled programs talk to the kernel directly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Arch {
+    /// assembled by fasm, or compiled straight into an executable
+    #[default]
+    X86_64,
+    /// assembled by GNU as
+    Aarch64,
+    /// assembled by GNU as, needs the M extension for multiplication
+    Riscv64,
+}
+
+/// parses target triples, like `aarch64-unknown-linux-gnu`, only the
+/// architecture and the system are looked at, and the system has to be linux
+impl TryFrom<&str> for Arch {
+    type Error = &'static str;
+
+    fn try_from(triple: &str) -> Result<Arch, Self::Error> {
+        let mut parts = triple.split('-');
+        let arch = match parts.next() {
+            Some("x86_64" | "amd64") => Arch::X86_64,
+            Some("aarch64" | "arm64") => Arch::Aarch64,
+            Some("riscv64" | "riscv64gc") => Arch::Riscv64,
+            _ => return Err("architecture must be one of x86_64, aarch64 or riscv64"),
+        };
+
+        match parts.any(|part| part == "linux") {
+            true => Ok(arch),
+            false => Err("only linux is supported, like in aarch64-linux-gnu"),
+        }
+    }
+}
+
+impl std::fmt::Display for Arch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Arch::X86_64 => write!(f, "x86_64-linux"),
+            Arch::Aarch64 => write!(f, "aarch64-linux"),
+            Arch::Riscv64 => write!(f, "riscv64-linux"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_linux_triples() {
+        assert_eq!(Arch::try_from("x86_64-unknown-linux-gnu"), Ok(Arch::X86_64));
+        assert_eq!(Arch::try_from("arm64-linux"), Ok(Arch::Aarch64));
+        assert_eq!(
+            Arch::try_from("riscv64gc-unknown-linux-gnu"),
+            Ok(Arch::Riscv64)
+        );
+        assert!(Arch::try_from("aarch64-apple-darwin").is_err());
+        assert!(Arch::try_from("i686-linux").is_err());
+    }
+}
@@ -0,0 +1,315 @@
+use parser::cell::CellWidth;
+use parser::eof::EofPolicy;
+use parser::error::{Error, RuntimeError};
+use parser::instruction_handler::InstructionHandler;
+use parser::program::Program;
+
+use crate::options::Options;
+
+use std::fmt::Write;
+
+/// `{cell}` must be replaced by the C type of a cell and `{tape_len}` by the
+/// amount of cells on the tape
+static PRELUDE: &str = "\
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+typedef {cell} cell;
+
+static cell tape[{tape_len}];
+";
+
+/// reads a single byte from stdin into `target`, `{eof}` must be replaced by
+/// what happens to it on the end of input
+static READ: &str = "
+static void read_cell(cell *target) {
+    int c = getchar();
+    if (c != EOF) {
+        *target = (cell)c;
+        return;
+    }
+    if (ferror(stdin)) {
+        fputs(\"failed to read from input\\n\", stderr);
+        exit(1);
+    }
+{eof}
+}
+";
+
+/// compiles programs into a single C source file, cells are unsigned integers
+/// as wide as the configured cell width, so they wrap around the same way they
+/// do on the interpreter
+///
+/// the body of `main` is kept until `finish`, as helpers have to be declared
+/// before it and we only know which ones are needed at the end
+#[derive(Debug, Default)]
+pub struct CSource {
+    options: Options,
+    body: String,
+    depth: usize,
+    has_read: bool,
+}
+
+impl CSource {
+    pub fn new(options: Options) -> CSource {
+        CSource {
+            options,
+            body: String::new(),
+            depth: 1,
+            has_read: false,
+        }
+    }
+
+    /// adds a statement to `main`, indented by how many loops it is in
+    fn line(&mut self, line: &str) {
+        // writing into a string never fails
+        _ = writeln!(self.body, "{:indent$}{}", "", line, indent = self.depth * 4);
+    }
+
+    /// the cell `offset` cells away from the data pointer
+    fn cell(offset: isize) -> String {
+        format!("p[{}]", offset)
+    }
+}
+
+impl<W, R> InstructionHandler<W, R> for CSource
+where
+    W: std::io::Write,
+    R: std::io::Read,
+{
+    fn add(
+        &mut self,
+        offset: isize,
+        value: i32,
+        _: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        match self.options.cell_width.wrap(value) {
+            0 => {}
+            value if value < 0 => self.line(&format!(
+                "{} -= {};",
+                CSource::cell(offset),
+                value.unsigned_abs()
+            )),
+            value => self.line(&format!("{} += {};", CSource::cell(offset), value)),
+        }
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn move_ptr(
+        &mut self,
+        amount: isize,
+        _: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        match amount {
+            amount if amount < 0 => self.line(&format!("p -= {};", amount.unsigned_abs())),
+            amount => self.line(&format!("p += {};", amount)),
+        }
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn write_ptr(
+        &mut self,
+        offset: isize,
+        count: usize,
+        _: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        for _ in 0..count {
+            self.line(&format!(
+                "putchar((unsigned char){});",
+                CSource::cell(offset)
+            ));
+        }
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn read_ptr(
+        &mut self,
+        offset: isize,
+        count: usize,
+        _: &Program,
+        _: &mut W,
+        _: &mut R,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        self.has_read = true;
+        for _ in 0..count {
+            self.line(&format!("read_cell(&{});", CSource::cell(offset)));
+        }
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn set_zero(
+        &mut self,
+        offset: isize,
+        _: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        self.line(&format!("{} = 0;", CSource::cell(offset)));
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn mul_add(
+        &mut self,
+        offset: isize,
+        factor: i32,
+        _: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        // narrow cells are promoted to `int`, where a big product overflows,
+        // so it is done on `unsigned long`, which is never promoted and wraps
+        // around, and only then truncated back to a cell
+        let cell = CSource::cell(offset);
+        let line = match factor {
+            1 => format!("{} += p[0];", cell),
+            -1 => format!("{} -= p[0];", cell),
+            factor => format!(
+                "{} = (cell)({} + (unsigned long)p[0] * (unsigned long){});",
+                cell, cell, factor
+            ),
+        };
+        self.line(&line);
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn scan(
+        &mut self,
+        step: isize,
+        _: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        match step {
+            step if step < 0 => self.line(&format!("while (p[0]) p -= {};", step.unsigned_abs())),
+            step => self.line(&format!("while (p[0]) p += {};", step)),
+        }
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn jump_if_zero(
+        &mut self,
+        _: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        self.line("while (p[0]) {");
+        self.depth += 1;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn jump_unless_zero(
+        &mut self,
+        _: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        // the body of main is the only block that isn't a loop
+        if self.depth == 1 {
+            return Err(RuntimeError::UnmatchedJump {
+                instruction_ptr: *instruction_ptr,
+            }
+            .into());
+        }
+        self.depth -= 1;
+        self.line("}");
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn dump(&mut self, _: &Program, _: &mut W, instruction_ptr: &mut usize) -> Result<(), Error> {
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self, writer: &mut W) -> Result<(), Error> {
+        let cell = match self.options.cell_width {
+            CellWidth::U8 => "uint8_t",
+            CellWidth::U16 => "uint16_t",
+            CellWidth::U32 => "uint32_t",
+        };
+        let prelude = PRELUDE.replace("{cell}", cell).replace(
+            "{tape_len}",
+            &self.options.tape_policy.fixed_len().to_string(),
+        );
+        write!(writer, "{}", prelude)?;
+
+        if self.has_read {
+            let eof = match self.options.eof_policy {
+                EofPolicy::Unchanged => "    /* the cell keeps its value */",
+                EofPolicy::Zero => "    *target = 0;",
+                EofPolicy::MinusOne => "    *target = (cell)-1;",
+            };
+            write!(writer, "{}", READ.replace("{eof}", eof))?;
+        }
+
+        writeln!(writer)?;
+        writeln!(writer, "int main(void) {{")?;
+        writeln!(
+            writer,
+            "    cell *p = tape + {};",
+            self.options.tape_policy.origin()
+        )?;
+        write!(writer, "{}", self.body)?;
+        writeln!(writer, "    return 0;")?;
+        writeln!(writer, "}}")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_text;
+
+    #[test]
+    fn loops_are_nested_blocks() {
+        let source = generate_text("+[>+[.>]<-]", |_| CSource::new(Options::default()));
+        assert!(source.contains("typedef uint8_t cell;"));
+        assert!(source.contains(
+            "    while (p[0]) {\n        p[1] += 1;\n        p += 1;\n        while (p[0]) {\n"
+        ));
+        // the helper to read is only there when something is read
+        assert!(!source.contains("read_cell"));
+    }
+
+    #[test]
+    fn reading_follows_the_eof_policy() {
+        let options = Options {
+            cell_width: CellWidth::U16,
+            eof_policy: EofPolicy::MinusOne,
+            ..Default::default()
+        };
+        let source = generate_text(",.", |_| CSource::new(options));
+        assert!(source.contains("typedef uint16_t cell;"));
+        assert!(source.contains("    *target = (cell)-1;"));
+        assert!(source.contains("    read_cell(&p[0]);\n    putchar((unsigned char)p[0]);\n"));
+    }
+
+    #[test]
+    fn products_do_not_overflow_int() {
+        let options = Options {
+            cell_width: CellWidth::U16,
+            ..Default::default()
+        };
+        let source = generate_text("+[->---<]", |_| CSource::new(options));
+        assert!(
+            source.contains("    p[1] = (cell)(p[1] + (unsigned long)p[0] * (unsigned long)-3);\n")
+        );
+    }
+}
@@ -1,36 +1,101 @@
-use lexer::token::Token;
+use parser::cell::CellWidth;
+use parser::eof::EofPolicy;
+use parser::error::Error;
 use parser::instruction_handler::InstructionHandler;
+use parser::program::Program;
 
-use crate::fasm_boilerplate::{
-    BREAK, FASM_BOILERPLATE, MULTI_DEC, MULTI_INC, MULTI_LEFT, MULTI_RIGHT, WRITE,
-};
+use crate::fasm_boilerplate::{FASM_BOILERPLATE, READ, WRITE};
+use crate::loops::OpenLoops;
+use crate::options::Options;
 
 #[derive(Debug, Default)]
 pub struct Compiler {
-    has_multi_increment: bool,
-    has_multi_decrement: bool,
-    has_multi_move_left: bool,
-    has_multi_move_right: bool,
+    options: Options,
     has_write: bool,
-    jump_count: usize,
-    jump_list: Vec<String>,
+    has_read: bool,
+    scan_count: usize,
+    loops: OpenLoops<usize>,
 }
 
 impl Compiler {
-    pub fn new<W>(writer: &mut W) -> Compiler
+    pub fn new<W>(writer: &mut W, options: Options) -> std::io::Result<Compiler>
     where
         W: std::io::Write,
     {
-        _ = write!(writer, "{}", FASM_BOILERPLATE);
-        Compiler {
-            has_multi_increment: false,
-            has_multi_decrement: false,
-            has_multi_move_left: false,
-            has_multi_move_right: false,
+        let boilerplate = FASM_BOILERPLATE
+            .replace("{cell_size}", &options.cell_width.bytes().to_string())
+            .replace("{tape_len}", &options.tape_policy.fixed_len().to_string())
+            .replace("{origin}", &options.tape_policy.origin().to_string());
+        write!(writer, "{}", boilerplate)?;
+        Ok(Compiler {
+            options,
             has_write: false,
-            jump_count: 0,
-            jump_list: vec![],
+            has_read: false,
+            scan_count: 0,
+            loops: OpenLoops::default(),
+        })
+    }
+
+    /// the operand size keyword matching the configured cell width
+    fn size(&self) -> &'static str {
+        match self.options.cell_width {
+            CellWidth::U8 => "byte",
+            CellWidth::U16 => "word",
+            CellWidth::U32 => "dword",
+        }
+    }
+
+    /// the part of `eax` that is as wide as a cell
+    fn accumulator(&self) -> &'static str {
+        match self.options.cell_width {
+            CellWidth::U8 => "al",
+            CellWidth::U16 => "ax",
+            CellWidth::U32 => "eax",
+        }
+    }
+
+    /// the address of the cell `offset` cells away from the data pointer, cells
+    /// are `cell_width` bytes apart from each other
+    fn address(&self, offset: isize) -> String {
+        let offset = offset * self.options.cell_width.bytes() as isize;
+        match offset {
+            0 => "[rbx]".to_string(),
+            offset if offset < 0 => format!("[rbx-{}]", offset.unsigned_abs()),
+            offset => format!("[rbx+{}]", offset),
+        }
+    }
+
+    /// the sized memory operand of the cell `offset` cells away from the data
+    /// pointer
+    fn cell(&self, offset: isize) -> String {
+        format!("{} {}", self.size(), self.address(offset))
+    }
+
+    /// loads the cell `offset` cells away from the data pointer into `eax`
+    fn load<W>(&self, offset: isize, writer: &mut W) -> Result<(), Error>
+    where
+        W: std::io::Write,
+    {
+        match self.options.cell_width {
+            CellWidth::U32 => writeln!(writer, "    mov eax, {}", self.cell(offset))?,
+            _ => writeln!(writer, "    movzx eax, {}", self.cell(offset))?,
         }
+        Ok(())
+    }
+
+    /// moves the data pointer by `amount` cells
+    fn move_by<W>(&self, amount: isize, writer: &mut W) -> Result<(), Error>
+    where
+        W: std::io::Write,
+    {
+        let amount = amount * self.options.cell_width.bytes() as isize;
+        match amount {
+            1 => writeln!(writer, "    inc rbx")?,
+            -1 => writeln!(writer, "    dec rbx")?,
+            amount if amount < 0 => writeln!(writer, "    sub rbx, {}", amount.unsigned_abs())?,
+            amount => writeln!(writer, "    add rbx, {}", amount)?,
+        }
+        Ok(())
     }
 }
 
@@ -39,234 +104,193 @@ where
     W: std::io::Write,
     R: std::io::Read,
 {
-    fn move_ptr_left(
+    fn add(
         &mut self,
-        count: usize,
-        _: &[Token],
+        offset: isize,
+        value: i32,
+        _: &Program,
         writer: &mut W,
         instruction_ptr: &mut usize,
-    ) {
-        if count > 1 {
-            self.has_multi_move_left = true;
-            _ = writeln!(writer, "    mov ecx, {}", count);
-            _ = writeln!(writer, "    call _l");
-        } else {
-            _ = writeln!(writer, "    dec ebx");
+    ) -> Result<(), Error> {
+        match self.options.cell_width.wrap(value) {
+            0 => {}
+            1 => writeln!(writer, "    inc {}", self.cell(offset))?,
+            -1 => writeln!(writer, "    dec {}", self.cell(offset))?,
+            value if value < 0 => writeln!(
+                writer,
+                "    sub {}, {}",
+                self.cell(offset),
+                value.unsigned_abs()
+            )?,
+            value => writeln!(writer, "    add {}, {}", self.cell(offset), value)?,
         }
+        *instruction_ptr += 1;
+        Ok(())
+    }
 
+    fn move_ptr(
+        &mut self,
+        amount: isize,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        self.move_by(amount, writer)?;
         *instruction_ptr += 1;
+        Ok(())
     }
 
-    fn move_ptr_right(
+    fn write_ptr(
         &mut self,
+        offset: isize,
         count: usize,
-        _: &[Token],
+        _: &Program,
         writer: &mut W,
         instruction_ptr: &mut usize,
-    ) {
-        if count > 1 {
-            self.has_multi_move_right = true;
-            _ = writeln!(writer, "    mov ecx, {}", count);
-            _ = writeln!(writer, "    call _r");
-        } else {
-            _ = writeln!(writer, "    inc ebx");
+    ) -> Result<(), Error> {
+        self.has_write = true;
+        writeln!(writer, "    lea rsi, {}", self.address(offset))?;
+        for _ in 0..count {
+            writeln!(writer, "    call _w")?;
         }
         *instruction_ptr += 1;
+        Ok(())
     }
 
-    fn increment_ptr(
+    fn read_ptr(
         &mut self,
+        offset: isize,
         count: usize,
-        _: &[Token],
+        _: &Program,
         writer: &mut W,
+        _: &mut R,
         instruction_ptr: &mut usize,
-    ) {
-        if count > 1 {
-            self.has_multi_increment = true;
-            _ = writeln!(writer, "    mov ecx, {}", count);
-            _ = writeln!(writer, "    call _u");
-        } else {
-            _ = writeln!(writer, "    inc byte [ebx]");
+    ) -> Result<(), Error> {
+        self.has_read = true;
+        self.load(offset, writer)?;
+        for _ in 0..count {
+            writeln!(writer, "    call _rd")?;
         }
+        writeln!(
+            writer,
+            "    mov {}, {}",
+            self.cell(offset),
+            self.accumulator()
+        )?;
         *instruction_ptr += 1;
+        Ok(())
     }
 
-    fn decrement_ptr(
+    fn set_zero(
         &mut self,
-        count: usize,
-        _: &[Token],
+        offset: isize,
+        _: &Program,
         writer: &mut W,
         instruction_ptr: &mut usize,
-    ) {
-        if count > 1 {
-            self.has_multi_decrement = true;
-            _ = writeln!(writer, "    mov ecx, {}", count);
-            _ = writeln!(writer, "    call _d");
-        } else {
-            _ = writeln!(writer, "    dec byte [ebx]");
-        }
+    ) -> Result<(), Error> {
+        writeln!(writer, "    mov {}, 0", self.cell(offset))?;
         *instruction_ptr += 1;
+        Ok(())
     }
 
-    fn write_ptr(
+    fn mul_add(
         &mut self,
-        count: usize,
-        _: &[Token],
+        offset: isize,
+        factor: i32,
+        _: &Program,
         writer: &mut W,
         instruction_ptr: &mut usize,
-    ) {
-        self.has_write = true;
-        for _ in 0..count {
-            _ = writeln!(writer, "    call _w");
+    ) -> Result<(), Error> {
+        self.load(0, writer)?;
+        match factor {
+            1 => {}
+            -1 => writeln!(writer, "    neg eax")?,
+            factor => writeln!(writer, "    imul eax, eax, {}", factor)?,
         }
+        writeln!(
+            writer,
+            "    add {}, {}",
+            self.cell(offset),
+            self.accumulator()
+        )?;
         *instruction_ptr += 1;
+        Ok(())
     }
 
-    fn read_ptr(
+    fn scan(
         &mut self,
-        _: usize,
-        _: &[Token],
-        _: &mut W,
-        _: &mut R,
+        step: isize,
+        _: &Program,
+        writer: &mut W,
         instruction_ptr: &mut usize,
-    ) {
+    ) -> Result<(), Error> {
+        let scan_name = format!("_s{}", self.scan_count);
+        self.scan_count += 1;
+        writeln!(writer, "{}:", scan_name)?;
+        writeln!(writer, "    cmp {}, 0", self.cell(0))?;
+        writeln!(writer, "    je {}e", scan_name)?;
+        self.move_by(step, writer)?;
+        writeln!(writer, "    jmp {}", scan_name)?;
+        writeln!(writer, "{}e:", scan_name)?;
         *instruction_ptr += 1;
+        Ok(())
     }
 
+    /// loops are compiled inline, the head skips over the loop when the
+    /// current cell is zero, and the tail jumps back to right after the head
+    /// while it isn't
     fn jump_if_zero(
         &mut self,
-        _: usize,
-        tokens: &[Token],
+        _: &Program,
         writer: &mut W,
         instruction_ptr: &mut usize,
-    ) {
-        let jump_header_name = format!("_j{}z", self.jump_count);
-        let jump_body_name = format!("_j{}b", self.jump_count);
-        self.jump_count += 1;
-        _ = writeln!(writer, "    call {}", jump_header_name);
-
-        let mut jump_header = String::new();
-        jump_header.push_str(&format!("{}:\n", jump_header_name));
-        jump_header.push_str("    cmp byte [ebx], 0\n");
-        jump_header.push_str("    je _b\n");
-        jump_header.push_str(&format!("    jne {}", jump_body_name));
-
-        let mut open_jumps = 1;
+    ) -> Result<(), Error> {
+        let jump = self.loops.opened();
+        self.loops.open(jump);
+        writeln!(writer, "    cmp {}, 0", self.cell(0))?;
+        writeln!(writer, "    jz _j{}e", jump)?;
+        writeln!(writer, "_j{}b:", jump)?;
         *instruction_ptr += 1;
-
-        let mut body_writer = vec![];
-        let mut cursor = std::io::Cursor::new(&mut body_writer);
-
-        while open_jumps != 0 {
-            if *instruction_ptr >= tokens.len() {
-                break;
-            }
-
-            match tokens[*instruction_ptr] {
-                Token::MoveLeft(count) => {
-                    <crate::Compiler as parser::instruction_handler::InstructionHandler<
-                        std::io::Cursor<&mut Vec<_>>,
-                        R,
-                    >>::move_ptr_left(
-                        self, count, tokens, &mut cursor, instruction_ptr
-                    );
-                }
-                Token::MoveRight(count) => {
-                    <crate::Compiler as parser::instruction_handler::InstructionHandler<
-                        std::io::Cursor<&mut Vec<_>>,
-                        R,
-                    >>::move_ptr_right(
-                        self, count, tokens, &mut cursor, instruction_ptr
-                    );
-                }
-                Token::Increment(count) => {
-                    <crate::Compiler as parser::instruction_handler::InstructionHandler<
-                        std::io::Cursor<&mut Vec<_>>,
-                        R,
-                    >>::increment_ptr(
-                        self, count, tokens, &mut cursor, instruction_ptr
-                    );
-                }
-                Token::Decrement(count) => {
-                    <crate::Compiler as parser::instruction_handler::InstructionHandler<
-                        std::io::Cursor<&mut Vec<_>>,
-                        R,
-                    >>::decrement_ptr(
-                        self, count, tokens, &mut cursor, instruction_ptr
-                    );
-                }
-                Token::Write(count) => {
-                    <crate::Compiler as parser::instruction_handler::InstructionHandler<
-                        std::io::Cursor<&mut Vec<_>>,
-                        R,
-                    >>::write_ptr(
-                        self, count, tokens, &mut cursor, instruction_ptr
-                    );
-                }
-                Token::JumpIfZero(count) => {
-                    <crate::Compiler as parser::instruction_handler::InstructionHandler<
-                        std::io::Cursor<&mut Vec<_>>,
-                        R,
-                    >>::jump_if_zero(
-                        self, count, tokens, &mut cursor, instruction_ptr
-                    );
-                }
-                Token::JumpUnlessZero(count) => {
-                    open_jumps -= count;
-                    *instruction_ptr += 1;
-                }
-                Token::Read(_) => *instruction_ptr += 1,
-            };
-        }
-
-        let mut jump_body = String::new();
-        jump_body.push_str(&format!("{}:\n", jump_body_name));
-
-        let resulting = String::from_utf8(body_writer).unwrap();
-        jump_body.push_str(&resulting);
-        jump_body.push_str("    cmp byte [ebx], 0\n");
-        jump_body.push_str(&format!("    jne {}\n", jump_body_name));
-        jump_body.push_str("    je _b");
-
-        self.jump_list.push(jump_header);
-        self.jump_list.push(jump_body);
+        Ok(())
     }
 
-    fn jump_unless_zero(&mut self, _: usize, _: &[Token], _: &mut W, _: &mut usize) {
-        // we handle the entire jump on the start of the jump, so we should
-        // never reach this block
-        unreachable!();
+    fn jump_unless_zero(
+        &mut self,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let jump = self.loops.close(*instruction_ptr)?;
+        writeln!(writer, "    cmp {}, 0", self.cell(0))?;
+        writeln!(writer, "    jnz _j{}b", jump)?;
+        writeln!(writer, "_j{}e:", jump)?;
+        *instruction_ptr += 1;
+        Ok(())
     }
 
-    fn finish(&mut self, writer: &mut W) {
-        _ = writeln!(writer, "    jmp _e");
-
-        for jump in self.jump_list.iter() {
-            _ = writeln!(writer, "{}", jump);
-        }
-
-        if self.has_multi_increment {
-            _ = writeln!(writer, "{}", MULTI_INC);
-        }
-
-        if self.has_multi_decrement {
-            _ = writeln!(writer, "{}", MULTI_DEC);
-        }
-
-        if self.has_multi_move_right {
-            _ = writeln!(writer, "{}", MULTI_RIGHT);
-        }
+    /// dumping is only meant for debugging on the interpreter, compiled
+    /// programs just skip over it
+    fn dump(&mut self, _: &Program, _: &mut W, instruction_ptr: &mut usize) -> Result<(), Error> {
+        *instruction_ptr += 1;
+        Ok(())
+    }
 
-        if self.has_multi_move_left {
-            _ = writeln!(writer, "{}", MULTI_LEFT);
-        }
+    fn finish(&mut self, writer: &mut W) -> Result<(), Error> {
+        writeln!(writer, "    jmp _e")?;
 
         if self.has_write {
-            _ = writeln!(writer, "{}", WRITE);
+            writeln!(writer, "{}", WRITE)?;
         }
 
-        if self.jump_count > 0 {
-            _ = writeln!(writer, "{}", BREAK);
+        if self.has_read {
+            let eof = match self.options.eof_policy {
+                EofPolicy::Unchanged => "    mov eax, r8d",
+                EofPolicy::Zero => "    xor eax, eax",
+                EofPolicy::MinusOne => "    mov eax, -1",
+            };
+            writeln!(writer, "{}", READ.replace("{eof}", eof))?;
         }
+
+        Ok(())
     }
 }
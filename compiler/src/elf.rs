@@ -0,0 +1,95 @@
+/// where the executable is loaded, the usual base address of non relocatable
+/// x86-64 executables on linux
+const BASE: u64 = 0x400000;
+const PAGE_SIZE: u64 = 0x1000;
+
+const ELF_HEADER_LEN: u64 = 64;
+const PROGRAM_HEADER_LEN: u64 = 56;
+/// the executable has two segments, one with the headers and the code, and one
+/// with the memory the program uses, which is zeroed by the kernel and takes no
+/// space in the file
+const PROGRAM_HEADER_COUNT: u64 = 2;
+const HEADERS_LEN: u64 = ELF_HEADER_LEN + PROGRAM_HEADER_COUNT * PROGRAM_HEADER_LEN;
+
+/// read and execute
+const CODE_FLAGS: u32 = 0b101;
+/// read and write
+const DATA_FLAGS: u32 = 0b110;
+
+/// where the code and the data of an executable end up in memory, the data
+/// comes after the code, so the code has to be done before anything can point
+/// into the data
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+    /// the address of the first byte of code, which is also the entry point
+    pub code: u64,
+    /// the address of the first byte of data
+    pub data: u64,
+}
+
+impl Layout {
+    pub fn new(code_len: usize) -> Layout {
+        let code = BASE + HEADERS_LEN;
+        let data = (code + code_len as u64).next_multiple_of(PAGE_SIZE);
+        Layout { code, data }
+    }
+}
+
+fn program_header<W>(
+    writer: &mut W,
+    flags: u32,
+    address: u64,
+    file_len: u64,
+    memory_len: u64,
+) -> std::io::Result<()>
+where
+    W: std::io::Write,
+{
+    // PT_LOAD
+    writer.write_all(&1u32.to_le_bytes())?;
+    writer.write_all(&flags.to_le_bytes())?;
+    // both segments start at the beginning of the file, the data segment just
+    // doesn't take anything from it
+    writer.write_all(&0u64.to_le_bytes())?;
+    writer.write_all(&address.to_le_bytes())?;
+    writer.write_all(&address.to_le_bytes())?;
+    writer.write_all(&file_len.to_le_bytes())?;
+    writer.write_all(&memory_len.to_le_bytes())?;
+    writer.write_all(&PAGE_SIZE.to_le_bytes())
+}
+
+/// writes an x86-64 linux executable that runs `code` from its first byte,
+/// with `data_len` zeroed bytes of memory at the data address of the layout of
+/// `code`
+pub fn write<W>(writer: &mut W, code: &[u8], data_len: usize) -> std::io::Result<()>
+where
+    W: std::io::Write,
+{
+    let layout = Layout::new(code.len());
+
+    // 64 bits, little endian, version 1, system v abi
+    writer.write_all(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0])?;
+    writer.write_all(&[0; 8])?;
+    // an executable for x86-64
+    writer.write_all(&2u16.to_le_bytes())?;
+    writer.write_all(&0x3eu16.to_le_bytes())?;
+    writer.write_all(&1u32.to_le_bytes())?;
+    writer.write_all(&layout.code.to_le_bytes())?;
+    // the program headers come right after this header, and there are no
+    // section headers
+    writer.write_all(&ELF_HEADER_LEN.to_le_bytes())?;
+    writer.write_all(&0u64.to_le_bytes())?;
+    writer.write_all(&0u32.to_le_bytes())?;
+    writer.write_all(&(ELF_HEADER_LEN as u16).to_le_bytes())?;
+    writer.write_all(&(PROGRAM_HEADER_LEN as u16).to_le_bytes())?;
+    writer.write_all(&(PROGRAM_HEADER_COUNT as u16).to_le_bytes())?;
+    writer.write_all(&64u16.to_le_bytes())?;
+    writer.write_all(&0u16.to_le_bytes())?;
+    writer.write_all(&0u16.to_le_bytes())?;
+
+    let code_len = HEADERS_LEN + code.len() as u64;
+    program_header(writer, CODE_FLAGS, BASE, code_len, code_len)?;
+    program_header(writer, DATA_FLAGS, layout.data, 0, data_len as u64)?;
+
+    writer.write_all(code)
+}
@@ -0,0 +1,55 @@
+use parser::error::{ParseError, RuntimeError};
+
+/// every error that can happen while compiling a program, on top of the errors
+/// of running a program through the `Parser`, some outputs can only be built
+/// with external tools, which might be missing or fail
+#[derive(Debug)]
+pub enum Error {
+    /// reading the source code or writing the output failed
+    Io(std::io::Error),
+    /// the source code is not a valid program
+    Parse(ParseError),
+    /// the compiler failed while going through the program
+    Runtime(RuntimeError),
+    /// the output needs an external toolchain to be built, which could not be
+    /// run or failed to build it
+    Toolchain(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Parse(e) => write!(f, "{}", e),
+            Error::Runtime(e) => write!(f, "{}", e),
+            Error::Toolchain(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            Error::Runtime(e) => Some(e),
+            Error::Toolchain(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<parser::error::Error> for Error {
+    fn from(e: parser::error::Error) -> Error {
+        match e {
+            parser::error::Error::Io(e) => Error::Io(e),
+            parser::error::Error::Parse(e) => Error::Parse(e),
+            parser::error::Error::Runtime(e) => Error::Runtime(e),
+        }
+    }
+}
@@ -1,14 +1,30 @@
+/// `{cell_size}` must be replaced by the size of a cell in bytes, `{tape_len}` by
+/// the amount of cells on the tape, and `{origin}` by the cell the data pointer
+/// starts at before being written, the tape always has `C` cells, no matter how
+/// wide they are
+///
+/// clearing the tape leaves `rbx` past its end, so it is moved back to the origin
+/// before the program starts
+///
+/// the data pointer and every address are 64 bits wide, and labels are reached
+/// relative to `rip`, so nothing depends on where the tape ends up being mapped
 pub static FASM_BOILERPLATE: &str = r#"format ELF64 executable 3
 entry _s
 Se equ 60
 Sw equ 1
-C equ 30000
+Sr equ 0
+C equ {tape_len}
+S equ {cell_size}
+O equ {origin}
 segment writeable
-    tape rb C
+    tape rb C*S
+    input rb 1
+    read_err_msg db "failed to read from input", 10
+    read_err_len = $ - read_err_msg
 segment executable
 _i:
-    mov byte [ebx], 0
-    inc ebx
+    mov byte [rbx], 0
+    inc rbx
     loop _i
     ret
 _e:
@@ -16,38 +32,49 @@ _e:
     xor edi, edi
     syscall
 _s:
-    mov ebx, tape
-    mov ecx, C
+    lea rbx, [tape]
+    mov rcx, C*S
     call _i
+    lea rbx, [tape+O*S]
 "#;
 
-pub static MULTI_INC: &str = r#"_u:
-    inc byte [ebx]
-    loop _u
-    ret"#;
-
-pub static MULTI_DEC: &str = r#"_d:
-    dec byte [ebx]
-    loop _d
-    ret"#;
-
-pub static MULTI_RIGHT: &str = r#"_r:
-    inc ebx
-    loop _r
-    ret"#;
-
-pub static MULTI_LEFT: &str = r#"_l:
-    dec ebx
-    loop _l
-    ret"#;
-
+/// writes the cell pointed by `rsi` to stdout, cells can be addressed with an
+/// offset from the data pointer, so the caller has to set `rsi` beforehand
 pub static WRITE: &str = r#"_w:
     mov eax, Sw
     mov edi, 1
-    mov esi, ebx
     mov edx, 1
     syscall
     ret"#;
 
-pub static BREAK: &str = r#"_b:
-    ret"#;
+/// reads a single byte from stdin and returns it zero extended in `eax`, so the
+/// caller can store it into a cell of any width, the caller must load the current
+/// value of the cell into `eax` beforehand, as reaching the end of input is handled
+/// by `{eof}`, which must be replaced by the instructions that set `eax` according
+/// to the eof policy, with the cell previous value available in `r8d`
+///
+/// when the read syscall fails we bail out through `_f`
+pub static READ: &str = r#"_rd:
+    mov r8d, eax
+    mov eax, Sr
+    xor edi, edi
+    lea rsi, [input]
+    mov edx, 1
+    syscall
+    test eax, eax
+    js _f
+    jz _rz
+    movzx eax, byte [input]
+    ret
+_rz:
+{eof}
+    ret
+_f:
+    mov eax, Sw
+    mov edi, 2
+    lea rsi, [read_err_msg]
+    mov edx, read_err_len
+    syscall
+    mov eax, Se
+    mov edi, 1
+    syscall"#;
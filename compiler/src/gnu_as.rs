@@ -0,0 +1,389 @@
+use parser::cell::CellWidth;
+use parser::eof::EofPolicy;
+use parser::error::Error;
+use parser::instruction_handler::InstructionHandler;
+use parser::program::Program;
+
+use crate::loops::OpenLoops;
+use crate::options::Options;
+
+use std::io::Write;
+use std::marker::PhantomData;
+
+/// how an architecture spells what `GnuAs` needs, every architecture compiles
+/// programs the same way otherwise
+///
+/// both GNU as syntaxes spell `neg`, `mul` and `add` between registers the
+/// same, so those are written as they are
+pub trait Isa {
+    /// the start of the program, which points `DATA_PTR` at the start of the
+    /// tape, `{tape_size}` must be replaced by the size of the tape in bytes
+    const PRELUDE: &'static str;
+    /// the `_w` routine, which writes the cell `WRITE_ARG` points to
+    const WRITE: &'static str;
+    /// the `_rd` routine, which works like the x86-64 `READ`, with the cell in
+    /// `CELL`, `{eof}` must be replaced by what `eof` returns
+    const READ: &'static str;
+    /// exits the program successfully
+    const EXIT: &'static str;
+
+    /// the data pointer, which has to survive syscalls and calls to `_w` and
+    /// `_rd`
+    const DATA_PTR: &'static str;
+    /// where the current cell is worked on
+    const CELL: &'static str;
+    /// where the cell `mul_add` adds to is worked on
+    const OTHER: &'static str;
+    /// where the factor of `mul_add` goes
+    const FACTOR: &'static str;
+    /// where `_w` expects the address of the cell to write
+    const WRITE_ARG: &'static str;
+    /// always holds 0
+    const ZERO: &'static str;
+
+    /// the instructions that apply `policy` to `CELL` once the input ends
+    fn eof(policy: EofPolicy) -> &'static str;
+
+    /// emits `reg = value`
+    fn constant<W>(writer: &mut W, reg: &str, value: i64) -> std::io::Result<()>
+    where
+        W: Write;
+
+    /// emits `dest = src + value`, going through a scratch register of its own
+    /// when `value` doesn't fit in an immediate
+    fn add_immediate<W>(writer: &mut W, dest: &str, src: &str, value: i64) -> std::io::Result<()>
+    where
+        W: Write;
+
+    /// emits the instructions to get to the byte `offset` bytes away from the
+    /// data pointer, and returns the memory operand for it, an `offset` of 0
+    /// never emits anything
+    fn address<W>(writer: &mut W, offset: isize) -> std::io::Result<String>
+    where
+        W: Write;
+
+    /// loads the cell at `address` zero extended into `reg`
+    fn load<W>(writer: &mut W, width: CellWidth, reg: &str, address: &str) -> std::io::Result<()>
+    where
+        W: Write;
+
+    /// stores the lowest bits of `reg` into the cell at `address`
+    fn store<W>(writer: &mut W, width: CellWidth, reg: &str, address: &str) -> std::io::Result<()>
+    where
+        W: Write;
+
+    /// branches to `label` when `reg` is 0, only nearby labels are in reach
+    fn branch_if_zero<W>(writer: &mut W, reg: &str, label: &str) -> std::io::Result<()>
+    where
+        W: Write;
+
+    /// branches to `label` when `reg` isn't 0, only nearby labels are in reach
+    fn branch_unless_zero<W>(writer: &mut W, reg: &str, label: &str) -> std::io::Result<()>
+    where
+        W: Write;
+
+    /// jumps to `label`, which may be much further than a branch reaches
+    fn jump<W>(writer: &mut W, label: &str) -> std::io::Result<()>
+    where
+        W: Write;
+
+    fn call<W>(writer: &mut W, label: &str) -> std::io::Result<()>
+    where
+        W: Write;
+}
+
+/// compiles programs into linux assembly for GNU as, on any architecture that
+/// implements `Isa`
+///
+/// conditional branches don't reach far on any of them, so loops branch over
+/// a jump, which reaches much further, instead of branching to the other end
+/// directly
+#[derive(Debug, Default)]
+pub struct GnuAs<I> {
+    options: Options,
+    has_write: bool,
+    has_read: bool,
+    scan_count: usize,
+    loops: OpenLoops<usize>,
+    isa: PhantomData<I>,
+}
+
+impl<I> GnuAs<I>
+where
+    I: Isa,
+{
+    pub fn new<W>(writer: &mut W, options: Options) -> std::io::Result<GnuAs<I>>
+    where
+        W: Write,
+    {
+        let tape_size = options.tape_policy.fixed_len() * options.cell_width.bytes();
+        write!(
+            writer,
+            "{}",
+            I::PRELUDE.replace("{tape_size}", &tape_size.to_string())
+        )?;
+        let origin = options.tape_policy.origin() * options.cell_width.bytes();
+        I::add_immediate(writer, I::DATA_PTR, I::DATA_PTR, origin as i64)?;
+
+        Ok(GnuAs {
+            options,
+            has_write: false,
+            has_read: false,
+            scan_count: 0,
+            loops: OpenLoops::default(),
+            isa: PhantomData,
+        })
+    }
+
+    /// the memory operand of the cell `offset` cells away from the data pointer
+    fn address<W>(&self, writer: &mut W, offset: isize) -> std::io::Result<String>
+    where
+        W: Write,
+    {
+        I::address(writer, offset * self.options.cell_width.bytes() as isize)
+    }
+
+    fn load<W>(&self, writer: &mut W, reg: &str, address: &str) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        I::load(writer, self.options.cell_width, reg, address)
+    }
+
+    fn store<W>(&self, writer: &mut W, reg: &str, address: &str) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        I::store(writer, self.options.cell_width, reg, address)
+    }
+
+    /// loads the current cell into `CELL`
+    fn load_current<W>(&self, writer: &mut W) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        let address = self.address(writer, 0)?;
+        self.load(writer, I::CELL, &address)
+    }
+}
+
+impl<W, R, I> InstructionHandler<W, R> for GnuAs<I>
+where
+    W: Write,
+    R: std::io::Read,
+    I: Isa + std::fmt::Debug,
+{
+    fn add(
+        &mut self,
+        offset: isize,
+        value: i32,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let value = self.options.cell_width.wrap(value);
+        if value != 0 {
+            let address = self.address(writer, offset)?;
+            self.load(writer, I::CELL, &address)?;
+            I::add_immediate(writer, I::CELL, I::CELL, value as i64)?;
+            self.store(writer, I::CELL, &address)?;
+        }
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn move_ptr(
+        &mut self,
+        amount: isize,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let amount = amount * self.options.cell_width.bytes() as isize;
+        I::add_immediate(writer, I::DATA_PTR, I::DATA_PTR, amount as i64)?;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn write_ptr(
+        &mut self,
+        offset: isize,
+        count: usize,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        self.has_write = true;
+        let offset = offset * self.options.cell_width.bytes() as isize;
+        I::add_immediate(writer, I::WRITE_ARG, I::DATA_PTR, offset as i64)?;
+        for _ in 0..count {
+            I::call(writer, "_w")?;
+        }
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn read_ptr(
+        &mut self,
+        offset: isize,
+        count: usize,
+        _: &Program,
+        writer: &mut W,
+        _: &mut R,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        self.has_read = true;
+        let address = self.address(writer, offset)?;
+        self.load(writer, I::CELL, &address)?;
+        for _ in 0..count {
+            I::call(writer, "_rd")?;
+        }
+        self.store(writer, I::CELL, &address)?;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn set_zero(
+        &mut self,
+        offset: isize,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let address = self.address(writer, offset)?;
+        self.store(writer, I::ZERO, &address)?;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn mul_add(
+        &mut self,
+        offset: isize,
+        factor: i32,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        self.load_current(writer)?;
+        match factor {
+            1 => {}
+            -1 => writeln!(writer, "    neg {}, {}", I::CELL, I::CELL)?,
+            factor => {
+                I::constant(writer, I::FACTOR, factor as i64)?;
+                writeln!(writer, "    mul {}, {}, {}", I::CELL, I::CELL, I::FACTOR)?;
+            }
+        }
+        let address = self.address(writer, offset)?;
+        self.load(writer, I::OTHER, &address)?;
+        writeln!(writer, "    add {}, {}, {}", I::OTHER, I::OTHER, I::CELL)?;
+        self.store(writer, I::OTHER, &address)?;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn scan(
+        &mut self,
+        step: isize,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let scan_name = format!("_s{}", self.scan_count);
+        self.scan_count += 1;
+        writeln!(writer, "{}:", scan_name)?;
+        self.load_current(writer)?;
+        I::branch_if_zero(writer, I::CELL, &format!("{}e", scan_name))?;
+        let step = step * self.options.cell_width.bytes() as isize;
+        I::add_immediate(writer, I::DATA_PTR, I::DATA_PTR, step as i64)?;
+        I::jump(writer, &scan_name)?;
+        writeln!(writer, "{}e:", scan_name)?;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn jump_if_zero(
+        &mut self,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let jump = self.loops.opened();
+        self.loops.open(jump);
+        self.load_current(writer)?;
+        I::branch_unless_zero(writer, I::CELL, &format!("_j{}b", jump))?;
+        I::jump(writer, &format!("_j{}e", jump))?;
+        writeln!(writer, "_j{}b:", jump)?;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn jump_unless_zero(
+        &mut self,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let jump = self.loops.close(*instruction_ptr)?;
+        self.load_current(writer)?;
+        I::branch_if_zero(writer, I::CELL, &format!("_j{}e", jump))?;
+        I::jump(writer, &format!("_j{}b", jump))?;
+        writeln!(writer, "_j{}e:", jump)?;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn dump(&mut self, _: &Program, _: &mut W, instruction_ptr: &mut usize) -> Result<(), Error> {
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self, writer: &mut W) -> Result<(), Error> {
+        writeln!(writer, "{}", I::EXIT)?;
+
+        if self.has_write {
+            writeln!(writer, "{}", I::WRITE)?;
+        }
+
+        if self.has_read {
+            let eof = I::eof(self.options.eof_policy);
+            writeln!(writer, "{}", I::READ.replace("{eof}", eof))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::aarch64::Aarch64;
+    use crate::generate_text;
+    use crate::riscv64::Riscv64;
+
+    /// echoes its input, then writes `A`
+    fn runs<I>(arch: &str)
+    where
+        I: Isa + std::fmt::Debug,
+    {
+        let options = Options {
+            cell_width: CellWidth::U16,
+            eof_policy: EofPolicy::Zero,
+            ..Default::default()
+        };
+        let source = ",[.,]++++++++[>++++++++<-]>+.";
+        let assembly = generate_text(source, |writer| GnuAs::<I>::new(writer, options).unwrap());
+        assert_eq!(crate::run_assembly(&assembly, arch, b"hi"), b"hiA");
+    }
+
+    #[test]
+    #[ignore = "needs the aarch64 cross binutils, and qemu off aarch64"]
+    fn aarch64_assembly_runs() {
+        runs::<Aarch64>("aarch64");
+    }
+
+    #[test]
+    #[ignore = "needs the riscv64 cross binutils, and qemu off riscv64"]
+    fn riscv64_assembly_runs() {
+        runs::<Riscv64>("riscv64");
+    }
+}
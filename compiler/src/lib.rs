@@ -1,87 +1,263 @@
+mod aarch64;
+mod arch;
+mod c;
 mod compiler;
+mod elf;
+mod error;
 mod fasm_boilerplate;
+mod gnu_as;
+mod llvm;
+mod loops;
+mod machine_code;
+mod options;
+mod riscv64;
+mod target;
+mod wat;
+use aarch64::Aarch64;
+pub use arch::Arch;
+use c::CSource;
 use compiler::Compiler;
+pub use error::Error;
+use gnu_as::GnuAs;
+use llvm::LlvmIr;
+use machine_code::MachineCode;
+pub use options::Options;
+use parser::instruction_handler::InstructionHandler;
+use parser::program::Program;
+use riscv64::Riscv64;
+pub use target::Target;
+use wat::Wat;
 
+use std::io::Write;
 use std::path::Path;
 
-pub fn assemble<P>(source: P, output: Option<String>)
+/// runs `program` through `handler`, which writes whatever it compiles the
+/// program into to `writer`
+fn generate<W, H>(program: Program, writer: W, handler: H) -> Result<(), Error>
 where
-    P: AsRef<Path>,
+    W: Write,
+    H: InstructionHandler<W, std::io::Empty> + std::fmt::Debug,
 {
-    let code = get_source_code(source);
-    let tokens = tokenize(&code);
-    let mut writer = create_output(output.unwrap_or("output.s".to_string()));
-    let compiler = Compiler::new(&mut writer);
-    parser::Parser::new(writer, std::io::stdin(), compiler).interpret(tokens)
+    let mut parser = parser::Parser::new(writer, std::io::empty(), handler);
+    parser.interpret(program)?;
+    parser.writer_mut().flush()?;
+    Ok(())
 }
 
-pub fn compile<P>(source: P, output: Option<String>)
+/// runs `source`, optimized, through the handler `new` builds out of the writer,
+/// which gets the writer in case the handler writes a prelude, and returns
+/// everything that was written, this is how every backend is tested
+#[cfg(test)]
+fn generate_bytes<H, F>(source: &str, new: F) -> Vec<u8>
 where
-    P: AsRef<Path>,
+    H: InstructionHandler<Vec<u8>, std::io::Empty> + std::fmt::Debug,
+    F: FnOnce(&mut Vec<u8>) -> H,
 {
-    assemble(source.as_ref(), Some("temp.s".to_string()));
+    let program = Program::parse(source).unwrap().optimize();
+    let mut writer = vec![];
+    let handler = new(&mut writer);
+    let mut parser = parser::Parser::new(writer, std::io::empty(), handler);
+    parser.interpret(program).unwrap();
+    parser.writer().clone()
+}
 
-    if let Err(e) = std::process::Command::new("fasm")
-        .arg("temp.s")
-        .arg(output.unwrap_or("output".to_string()))
-        .output()
+/// same as `generate_bytes`, for the backends that write text
+#[cfg(test)]
+fn generate_text<H, F>(source: &str, new: F) -> String
+where
+    H: InstructionHandler<Vec<u8>, std::io::Empty> + std::fmt::Debug,
+    F: FnOnce(&mut Vec<u8>) -> H,
+{
+    String::from_utf8(generate_bytes(source, new)).unwrap()
+}
+
+/// builds GNU as `assembly` for `arch` with its cross binutils, and runs it
+/// with `input`, under qemu unless we are on `arch` already, returning what it
+/// wrote
+///
+/// the tools are not around everywhere, so tests using this are ignored, and
+/// have to be asked for with `cargo test -- --ignored`
+#[cfg(all(test, target_os = "linux"))]
+fn run_assembly(assembly: &str, arch: &str, input: &[u8]) -> Vec<u8> {
+    use std::process::{Command, Stdio};
+
+    let assembler = format!("{}-linux-gnu-as", arch);
+    let linker = format!("{}-linux-gnu-ld", arch);
+    let runner = match std::env::consts::ARCH == arch {
+        true => None,
+        false => Some(format!("qemu-{}", arch)),
+    };
+    for tool in [Some(&assembler), Some(&linker), runner.as_ref()]
+        .into_iter()
+        .flatten()
     {
-        eprintln!("{}", e);
+        let installed = Command::new(tool).arg("--version").output().is_ok();
+        assert!(installed, "{} is not installed", tool);
     }
 
-    if std::fs::remove_file("temp.s").is_err() {
-        eprintln!("ERROR: failed to do cleanup");
-        std::process::exit(1);
+    let base = std::env::temp_dir().join(format!("bfck-{}-{}", arch, std::process::id()));
+    let source = base.with_extension("s");
+    let object = base.with_extension("o");
+    std::fs::write(&source, assembly).unwrap();
+    let assembled = Command::new(&assembler)
+        .arg(&source)
+        .arg("-o")
+        .arg(&object)
+        .status()
+        .unwrap();
+    assert!(assembled.success());
+    let linked = Command::new(&linker)
+        .arg(&object)
+        .arg("-o")
+        .arg(&base)
+        .status()
+        .unwrap();
+    assert!(linked.success());
+
+    let mut command = match &runner {
+        Some(runner) => Command::new(runner),
+        None => Command::new(&base),
+    };
+    if runner.is_some() {
+        command.arg(&base);
     }
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    let output = child.wait_with_output().unwrap();
+    for path in [&source, &object, &base] {
+        _ = std::fs::remove_file(path);
+    }
+
+    assert!(output.status.success());
+    output.stdout
 }
 
-fn tokenize(source: &str) -> Vec<lexer::token::Token> {
-    lexer::Lexer::tokenize(source)
+/// compiles `source` into source code for the target, which is assembly for
+/// native targets, fasm flavoured on x86-64 and GNU as flavoured elsewhere
+pub fn assemble<P>(source: P, output: Option<String>, options: Options) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+{
+    let program = Program::from_file(source)?.optimize();
+    match options.target {
+        Target::Native => {
+            let mut writer = create_output(output.unwrap_or("output.s".to_string()))?;
+            match options.arch {
+                Arch::X86_64 => {
+                    let compiler = Compiler::new(&mut writer, options)?;
+                    generate(program, writer, compiler)
+                }
+                Arch::Aarch64 => {
+                    let aarch64 = GnuAs::<Aarch64>::new(&mut writer, options)?;
+                    generate(program, writer, aarch64)
+                }
+                Arch::Riscv64 => {
+                    let riscv64 = GnuAs::<Riscv64>::new(&mut writer, options)?;
+                    generate(program, writer, riscv64)
+                }
+            }
+        }
+        Target::C => {
+            let writer = create_output(output.unwrap_or("output.c".to_string()))?;
+            generate(program, writer, CSource::new(options))
+        }
+        Target::Wat => {
+            let mut writer = create_output(output.unwrap_or("output.wat".to_string()))?;
+            let wat = Wat::new(&mut writer, options)?;
+            generate(program, writer, wat)
+        }
+        Target::Llvm => {
+            let mut writer = create_output(output.unwrap_or("output.ll".to_string()))?;
+            let llvm_ir = LlvmIr::new(&mut writer, options)?;
+            generate(program, writer, llvm_ir)
+        }
+    }
 }
 
-fn get_source_code<P>(path: P) -> String
+/// compiles `source` into an x86-64 linux executable, the machine code is
+/// generated and linked by us, so no assembler needs to be installed
+///
+/// targets that are source code of another language, and other architectures,
+/// can't be built without their toolchain, so compiling to them is an error,
+/// they have to be assembled and then built with their own toolchain
+pub fn compile<P>(source: P, output: Option<String>, options: Options) -> Result<(), Error>
 where
     P: AsRef<Path>,
 {
-    if !path.as_ref().exists() {
-        eprintln!(
-            "ERROR: cannot find {}: No such file or directory",
-            path.as_ref().to_string_lossy()
-        );
-        std::process::exit(1);
+    if options.target != Target::Native || options.arch != Arch::X86_64 {
+        let what = match options.target {
+            Target::Native => format!("{} executables", options.arch),
+            target => format!("the {} target", target),
+        };
+        return Err(Error::Toolchain(format!(
+            "{} can only be built with an external toolchain, use --assemble to get its source instead",
+            what
+        )));
     }
 
-    let Ok(code) = std::fs::read_to_string(path.as_ref()) else {
-        eprintln!("ERROR: failed to read {}", path.as_ref().to_string_lossy());
-        std::process::exit(1);
-    };
+    let program = Program::from_file(source)?.optimize();
+    let output = output.unwrap_or("output".to_string());
+    let writer = create_output(&output)?;
+    generate(program, writer, MachineCode::new(options))?;
+    make_executable(&output)
+}
 
-    code
+#[cfg(unix)]
+fn make_executable<P>(path: P) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+{
+    use std::os::unix::fs::PermissionsExt;
+
+    let permissions = std::fs::Permissions::from_mode(0o755);
+    std::fs::set_permissions(path, permissions).map_err(Error::from)
 }
 
-fn create_output<P>(path: P) -> impl std::io::Write
+#[cfg(not(unix))]
+fn make_executable<P>(_: P) -> Result<(), Error>
 where
     P: AsRef<Path>,
 {
-    let Ok(cwd) = std::env::current_dir() else {
-        eprintln!("ERROR: failed to get current working directory");
-        std::process::exit(1);
-    };
+    Ok(())
+}
 
-    let output = cwd.join(path.as_ref());
-    let Ok(output) = std::fs::OpenOptions::new()
+fn create_output<P>(path: P) -> Result<impl std::io::Write, Error>
+where
+    P: AsRef<Path>,
+{
+    let output = std::env::current_dir()?.join(path.as_ref());
+    let output = std::fs::OpenOptions::new()
         .create(true)
         .truncate(true)
         .read(true)
         .write(true)
-        .open(&output)
-    else {
-        eprintln!(
-            "ERROR: failed to create output file: {}",
-            output.to_string_lossy()
-        );
-        std::process::exit(1);
-    };
+        .open(&output)?;
+
+    Ok(std::io::BufWriter::new(output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    output
+    #[test]
+    fn only_x86_64_is_compiled_into_executables() {
+        let options = Options {
+            target: Target::Wat,
+            ..Default::default()
+        };
+        let result = compile("missing.bf", None, options);
+        assert!(matches!(result, Err(Error::Toolchain(_))));
+
+        let options = Options {
+            arch: Arch::Riscv64,
+            ..Default::default()
+        };
+        let result = compile("missing.bf", None, options);
+        assert!(matches!(result, Err(Error::Toolchain(_))));
+    }
 }
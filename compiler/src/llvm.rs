@@ -0,0 +1,420 @@
+use parser::cell::CellWidth;
+use parser::eof::EofPolicy;
+use parser::error::Error;
+use parser::instruction_handler::InstructionHandler;
+use parser::program::Program;
+
+use crate::loops::OpenLoops;
+use crate::options::Options;
+
+/// `{cell}` must be replaced by the integer type of a cell, `{tape_len}` by the
+/// amount of cells on the tape and `{origin}` by the cell the data pointer
+/// starts at
+static PRELUDE: &str = "\
+@tape = internal global [{tape_len} x {cell}] zeroinitializer
+
+declare i32 @getchar()
+declare i32 @putchar(i32)
+
+define i32 @main() {
+entry:
+  %p = alloca ptr
+  store ptr getelementptr inbounds ([{tape_len} x {cell}], ptr @tape, i64 0, i64 {origin}), ptr %p
+";
+
+/// compiles programs into textual LLVM IR, with the tape as a global array and
+/// io through `getchar` and `putchar`, so the result can be built and optimized
+/// by anything that takes LLVM IR, like `clang -O3 output.ll`
+///
+/// the data pointer lives on the stack, and every operation loads it, which
+/// keeps the IR simple, the optimizer turns it into a register anyway
+///
+/// `getchar` doesn't tell the end of input apart from failing to read, so both
+/// are handled by the eof policy
+#[derive(Debug, Default)]
+pub struct LlvmIr {
+    options: Options,
+    /// how many temporaries were used so far, every one of them needs a new name
+    temp_count: usize,
+    scan_count: usize,
+    loops: OpenLoops<usize>,
+}
+
+impl LlvmIr {
+    pub fn new<W>(writer: &mut W, options: Options) -> std::io::Result<LlvmIr>
+    where
+        W: std::io::Write,
+    {
+        let llvm_ir = LlvmIr {
+            options,
+            ..Default::default()
+        };
+        let prelude = PRELUDE
+            .replace("{cell}", &llvm_ir.cell_type())
+            .replace("{tape_len}", &options.tape_policy.fixed_len().to_string())
+            .replace("{origin}", &options.tape_policy.origin().to_string());
+        write!(writer, "{}", prelude)?;
+        Ok(llvm_ir)
+    }
+
+    fn cell_type(&self) -> String {
+        format!("i{}", self.options.cell_width.bits())
+    }
+
+    fn temp(&mut self) -> String {
+        self.temp_count += 1;
+        format!("%t{}", self.temp_count)
+    }
+
+    /// emits the instructions to get the address of the cell `offset` cells
+    /// away from the data pointer, and returns the temporary that holds it
+    fn address<W>(&mut self, offset: isize, writer: &mut W) -> Result<String, Error>
+    where
+        W: std::io::Write,
+    {
+        let pointer = self.temp();
+        writeln!(writer, "  {} = load ptr, ptr %p", pointer)?;
+        if offset == 0 {
+            return Ok(pointer);
+        }
+
+        let address = self.temp();
+        writeln!(
+            writer,
+            "  {} = getelementptr {}, ptr {}, i64 {}",
+            address,
+            self.cell_type(),
+            pointer,
+            offset
+        )?;
+        Ok(address)
+    }
+
+    /// emits the instructions to load the cell at `address`, and returns the
+    /// temporary that holds its value
+    fn load<W>(&mut self, address: &str, writer: &mut W) -> Result<String, Error>
+    where
+        W: std::io::Write,
+    {
+        let value = self.temp();
+        writeln!(
+            writer,
+            "  {} = load {}, ptr {}",
+            value,
+            self.cell_type(),
+            address
+        )?;
+        Ok(value)
+    }
+
+    fn store<W>(&self, address: &str, value: &str, writer: &mut W) -> Result<(), Error>
+    where
+        W: std::io::Write,
+    {
+        writeln!(
+            writer,
+            "  store {} {}, ptr {}",
+            self.cell_type(),
+            value,
+            address
+        )?;
+        Ok(())
+    }
+
+    /// emits a branch to `zero` when the current cell is zero, and to
+    /// `nonzero` otherwise
+    fn branch<W>(&mut self, zero: &str, nonzero: &str, writer: &mut W) -> Result<(), Error>
+    where
+        W: std::io::Write,
+    {
+        let address = self.address(0, writer)?;
+        let value = self.load(&address, writer)?;
+        let is_zero = self.temp();
+        writeln!(
+            writer,
+            "  {} = icmp eq {} {}, 0",
+            is_zero,
+            self.cell_type(),
+            value
+        )?;
+        writeln!(
+            writer,
+            "  br i1 {}, label %{}, label %{}",
+            is_zero, zero, nonzero
+        )?;
+        Ok(())
+    }
+}
+
+impl<W, R> InstructionHandler<W, R> for LlvmIr
+where
+    W: std::io::Write,
+    R: std::io::Read,
+{
+    fn add(
+        &mut self,
+        offset: isize,
+        value: i32,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let value = self.options.cell_width.wrap(value);
+        if value != 0 {
+            let address = self.address(offset, writer)?;
+            let old = self.load(&address, writer)?;
+            let new = self.temp();
+            writeln!(
+                writer,
+                "  {} = add {} {}, {}",
+                new,
+                self.cell_type(),
+                old,
+                value
+            )?;
+            self.store(&address, &new, writer)?;
+        }
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn move_ptr(
+        &mut self,
+        amount: isize,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let address = self.address(amount, writer)?;
+        writeln!(writer, "  store ptr {}, ptr %p", address)?;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn write_ptr(
+        &mut self,
+        offset: isize,
+        count: usize,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let address = self.address(offset, writer)?;
+        let value = self.load(&address, writer)?;
+        let char = match self.options.cell_width {
+            CellWidth::U32 => value,
+            _ => {
+                let extended = self.temp();
+                writeln!(
+                    writer,
+                    "  {} = zext {} {} to i32",
+                    extended,
+                    self.cell_type(),
+                    value
+                )?;
+                extended
+            }
+        };
+        for _ in 0..count {
+            let result = self.temp();
+            writeln!(writer, "  {} = call i32 @putchar(i32 {})", result, char)?;
+        }
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn read_ptr(
+        &mut self,
+        offset: isize,
+        count: usize,
+        _: &Program,
+        writer: &mut W,
+        _: &mut R,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let address = self.address(offset, writer)?;
+        for _ in 0..count {
+            let char = self.temp();
+            writeln!(writer, "  {} = call i32 @getchar()", char)?;
+            let is_eof = self.temp();
+            writeln!(writer, "  {} = icmp slt i32 {}, 0", is_eof, char)?;
+            let read = match self.options.cell_width {
+                CellWidth::U32 => char,
+                _ => {
+                    let truncated = self.temp();
+                    writeln!(
+                        writer,
+                        "  {} = trunc i32 {} to {}",
+                        truncated,
+                        char,
+                        self.cell_type()
+                    )?;
+                    truncated
+                }
+            };
+            let eof = match self.options.eof_policy {
+                EofPolicy::Unchanged => self.load(&address, writer)?,
+                EofPolicy::Zero => "0".to_string(),
+                EofPolicy::MinusOne => "-1".to_string(),
+            };
+            let value = self.temp();
+            writeln!(
+                writer,
+                "  {} = select i1 {}, {} {}, {} {}",
+                value,
+                is_eof,
+                self.cell_type(),
+                eof,
+                self.cell_type(),
+                read
+            )?;
+            self.store(&address, &value, writer)?;
+        }
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn set_zero(
+        &mut self,
+        offset: isize,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let address = self.address(offset, writer)?;
+        self.store(&address, "0", writer)?;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn mul_add(
+        &mut self,
+        offset: isize,
+        factor: i32,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let current = self.address(0, writer)?;
+        let value = self.load(&current, writer)?;
+        let product = match self.options.cell_width.wrap(factor) {
+            1 => value,
+            factor => {
+                let product = self.temp();
+                writeln!(
+                    writer,
+                    "  {} = mul {} {}, {}",
+                    product,
+                    self.cell_type(),
+                    value,
+                    factor
+                )?;
+                product
+            }
+        };
+
+        let address = self.address(offset, writer)?;
+        let old = self.load(&address, writer)?;
+        let new = self.temp();
+        writeln!(
+            writer,
+            "  {} = add {} {}, {}",
+            new,
+            self.cell_type(),
+            old,
+            product
+        )?;
+        self.store(&address, &new, writer)?;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn scan(
+        &mut self,
+        step: isize,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let scan = self.scan_count;
+        self.scan_count += 1;
+        writeln!(writer, "  br label %s{}", scan)?;
+        writeln!(writer, "s{}:", scan)?;
+        self.branch(&format!("s{}e", scan), &format!("s{}b", scan), writer)?;
+        writeln!(writer, "s{}b:", scan)?;
+        let address = self.address(step, writer)?;
+        writeln!(writer, "  store ptr {}, ptr %p", address)?;
+        writeln!(writer, "  br label %s{}", scan)?;
+        writeln!(writer, "s{}e:", scan)?;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn jump_if_zero(
+        &mut self,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let jump = self.loops.opened();
+        self.loops.open(jump);
+        self.branch(&format!("j{}e", jump), &format!("j{}b", jump), writer)?;
+        writeln!(writer, "j{}b:", jump)?;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn jump_unless_zero(
+        &mut self,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let jump = self.loops.close(*instruction_ptr)?;
+        self.branch(&format!("j{}e", jump), &format!("j{}b", jump), writer)?;
+        writeln!(writer, "j{}e:", jump)?;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn dump(&mut self, _: &Program, _: &mut W, instruction_ptr: &mut usize) -> Result<(), Error> {
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self, writer: &mut W) -> Result<(), Error> {
+        writeln!(writer, "  ret i32 0")?;
+        writeln!(writer, "}}")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_text;
+
+    #[test]
+    fn tape_is_a_global_array() {
+        let options = Options {
+            cell_width: CellWidth::U16,
+            ..Default::default()
+        };
+        let module = generate_text("-.", |writer| LlvmIr::new(writer, options).unwrap());
+        assert!(module.starts_with("@tape = internal global [30000 x i16] zeroinitializer\n"));
+        assert!(module.contains("  %t3 = add i16 %t2, -1\n"));
+        assert!(
+            module.contains("  %t6 = zext i16 %t5 to i32\n  %t7 = call i32 @putchar(i32 %t6)\n")
+        );
+        assert!(module.ends_with("  ret i32 0\n}\n"));
+    }
+
+    #[test]
+    fn loops_branch_on_the_current_cell() {
+        let module = generate_text("+[.-]", |writer| {
+            LlvmIr::new(writer, Options::default()).unwrap()
+        });
+        assert!(module.contains("  br i1 %t6, label %j0e, label %j0b\nj0b:\n"));
+        assert!(module.contains("  br i1 %t16, label %j0e, label %j0b\nj0e:\n"));
+    }
+}
@@ -0,0 +1,45 @@
+use parser::error::RuntimeError;
+
+/// the loops that are open at the current instruction, the innermost one last,
+/// every backend opens a loop with whatever it needs to close it later, like
+/// the number of its labels, and gets it back on the matching `]`
+#[derive(Debug)]
+pub struct OpenLoops<T> {
+    loops: Vec<T>,
+    /// how many loops were opened so far, which backends use to give every
+    /// loop its own labels
+    opened: usize,
+}
+
+impl<T> Default for OpenLoops<T> {
+    fn default() -> OpenLoops<T> {
+        OpenLoops {
+            loops: vec![],
+            opened: 0,
+        }
+    }
+}
+
+impl<T> OpenLoops<T> {
+    pub fn opened(&self) -> usize {
+        self.opened
+    }
+
+    /// how many loops the current instruction is in
+    pub fn depth(&self) -> usize {
+        self.loops.len()
+    }
+
+    pub fn open(&mut self, open: T) {
+        self.loops.push(open);
+        self.opened += 1;
+    }
+
+    /// closes the innermost loop, failing when there is none, as then the `]`
+    /// at `instruction_ptr` has no matching `[`
+    pub fn close(&mut self, instruction_ptr: usize) -> Result<T, RuntimeError> {
+        self.loops
+            .pop()
+            .ok_or(RuntimeError::UnmatchedJump { instruction_ptr })
+    }
+}
@@ -0,0 +1,488 @@
+use parser::cell::CellWidth;
+use parser::eof::EofPolicy;
+use parser::error::Error;
+use parser::instruction_handler::InstructionHandler;
+use parser::program::Program;
+
+use crate::elf::{self, Layout};
+use crate::loops::OpenLoops;
+use crate::options::Options;
+
+/// the syscalls compiled programs make
+const SYS_READ: u32 = 0;
+const SYS_WRITE: u32 = 1;
+const SYS_EXIT: u32 = 60;
+
+static READ_ERR_MSG: &[u8] = b"failed to read from input\n";
+
+/// the register numbers used in the ModRM byte
+const EAX: u8 = 0;
+const EDX: u8 = 2;
+const ESI: u8 = 6;
+const EDI: u8 = 7;
+
+/// a place in the code that jumps can go to, it only has an offset once the
+/// code at that place is emitted
+type Label = usize;
+
+/// an absolute address the code refers to, which is only known once all the
+/// code has been emitted, see `Layout`
+#[derive(Debug, Clone, Copy)]
+enum Address {
+    /// `n` bytes into the tape
+    Tape(u64),
+    /// the byte `read` reads into
+    Input,
+    /// somewhere in the code itself
+    Code(Label),
+}
+
+/// compiles programs straight into x86-64 machine code, and wraps it into an
+/// ELF executable, so no assembler is needed, the code does the same as the
+/// assembly the `Compiler` produces, with the data pointer in `rbx`
+///
+/// the whole executable is written on `finish`, as addresses into the code and
+/// the tape are only known once every operation has been compiled
+#[derive(Debug, Default)]
+pub struct MachineCode {
+    options: Options,
+    code: Vec<u8>,
+    /// the offset of every label into the code, `None` until it is placed
+    labels: Vec<Option<usize>>,
+    /// the offsets of the 32-bit relative jumps, and where they jump to
+    jumps: Vec<(usize, Label)>,
+    /// the offsets of the 64-bit absolute addresses, and what they point to
+    addresses: Vec<(usize, Address)>,
+    /// the labels of the start of the body and of the end of every open loop
+    loops: OpenLoops<(Label, Label)>,
+    write: Label,
+    read: Label,
+    has_write: bool,
+    has_read: bool,
+}
+
+impl MachineCode {
+    pub fn new(options: Options) -> MachineCode {
+        let mut machine_code = MachineCode {
+            options,
+            ..Default::default()
+        };
+        machine_code.write = machine_code.label();
+        machine_code.read = machine_code.label();
+
+        // mov rbx, tape+O*S
+        let origin = options.tape_policy.origin() * options.cell_width.bytes();
+        machine_code.emit(&[0x48, 0xbb]);
+        machine_code.address(Address::Tape(origin as u64));
+        machine_code
+    }
+
+    fn emit(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+
+    fn imm32(&mut self, value: u32) {
+        self.emit(&value.to_le_bytes());
+    }
+
+    /// an immediate as wide as a cell
+    fn immediate(&mut self, value: i32) {
+        match self.options.cell_width {
+            CellWidth::U8 => self.emit(&[value as u8]),
+            CellWidth::U16 => self.emit(&(value as u16).to_le_bytes()),
+            CellWidth::U32 => self.imm32(value as u32),
+        }
+    }
+
+    /// `mov reg, value`, for the 32-bit registers up to `edi`
+    fn mov_imm32(&mut self, reg: u8, value: u32) {
+        self.emit(&[0xb8 + reg]);
+        self.imm32(value);
+    }
+
+    fn syscall(&mut self) {
+        self.emit(&[0x0f, 0x05]);
+    }
+
+    fn label(&mut self) -> Label {
+        self.labels.push(None);
+        self.labels.len() - 1
+    }
+
+    fn place(&mut self, label: Label) {
+        self.labels[label] = Some(self.code.len());
+    }
+
+    /// emits `opcode` followed by the offset to `label`
+    fn jump(&mut self, opcode: &[u8], label: Label) {
+        self.emit(opcode);
+        self.jumps.push((self.code.len(), label));
+        self.imm32(0);
+    }
+
+    /// emits a placeholder for the absolute `address`
+    fn address(&mut self, address: Address) {
+        self.addresses.push((self.code.len(), address));
+        self.emit(&[0; 8]);
+    }
+
+    /// the ModRM byte and displacement of the cell `offset` cells away from the
+    /// data pointer, `reg` is either a register or an opcode extension
+    fn memory(&mut self, reg: u8, offset: isize) {
+        let displacement = offset * self.options.cell_width.bytes() as isize;
+        // mod 10, which takes a 32-bit displacement, and rm 011, which is rbx
+        self.emit(&[0b10_000_011 | reg << 3]);
+        self.imm32(displacement as i32 as u32);
+    }
+
+    /// an instruction on a cell, `byte_opcode` is used for 8-bit cells and
+    /// `opcode` for wider ones
+    fn cell(&mut self, byte_opcode: u8, opcode: u8, reg: u8, offset: isize) {
+        match self.options.cell_width {
+            CellWidth::U8 => self.emit(&[byte_opcode]),
+            CellWidth::U16 => self.emit(&[0x66, opcode]),
+            CellWidth::U32 => self.emit(&[opcode]),
+        }
+        self.memory(reg, offset);
+    }
+
+    /// `cmp cell, 0` on the current cell
+    fn compare_to_zero(&mut self) {
+        self.cell(0x80, 0x81, 7, 0);
+        self.immediate(0);
+    }
+
+    /// loads the cell `offset` cells away from the data pointer into `eax`
+    fn load(&mut self, offset: isize) {
+        match self.options.cell_width {
+            CellWidth::U8 => self.emit(&[0x0f, 0xb6]),
+            CellWidth::U16 => self.emit(&[0x0f, 0xb7]),
+            CellWidth::U32 => self.emit(&[0x8b]),
+        }
+        self.memory(EAX, offset);
+    }
+
+    /// moves the data pointer by `amount` cells
+    fn move_by(&mut self, amount: isize) {
+        let amount = amount * self.options.cell_width.bytes() as isize;
+        // add rbx, amount
+        self.emit(&[0x48, 0x81, 0xc3]);
+        self.imm32(amount as i32 as u32);
+    }
+
+    /// writes the cell pointed by `rsi` to stdout
+    fn write_routine(&mut self) {
+        self.place(self.write);
+        self.mov_imm32(EAX, SYS_WRITE);
+        self.mov_imm32(EDI, 1);
+        self.mov_imm32(EDX, 1);
+        self.syscall();
+        self.emit(&[0xc3]);
+    }
+
+    /// reads a single byte from stdin into `eax`, with the previous value of
+    /// the cell in `eax` beforehand, see `fasm_boilerplate::READ`
+    fn read_routine(&mut self) {
+        let eof = self.label();
+        let fail = self.label();
+        let message = self.label();
+
+        self.place(self.read);
+        // mov r8d, eax
+        self.emit(&[0x41, 0x89, 0xc0]);
+        self.mov_imm32(EAX, SYS_READ);
+        // xor edi, edi
+        self.emit(&[0x31, 0xff]);
+        // mov rsi, input
+        self.emit(&[0x48, 0xbe]);
+        self.address(Address::Input);
+        self.mov_imm32(EDX, 1);
+        self.syscall();
+        // test eax, eax, then js fail and jz eof
+        self.emit(&[0x85, 0xc0]);
+        self.jump(&[0x0f, 0x88], fail);
+        self.jump(&[0x0f, 0x84], eof);
+        // movzx eax, byte [rsi], then ret
+        self.emit(&[0x0f, 0xb6, 0x06, 0xc3]);
+
+        self.place(eof);
+        match self.options.eof_policy {
+            // mov eax, r8d
+            EofPolicy::Unchanged => self.emit(&[0x44, 0x89, 0xc0]),
+            // xor eax, eax
+            EofPolicy::Zero => self.emit(&[0x31, 0xc0]),
+            EofPolicy::MinusOne => self.mov_imm32(EAX, u32::MAX),
+        }
+        self.emit(&[0xc3]);
+
+        self.place(fail);
+        self.mov_imm32(EAX, SYS_WRITE);
+        self.mov_imm32(EDI, 2);
+        self.emit(&[0x48, 0xbe]);
+        self.address(Address::Code(message));
+        self.mov_imm32(EDX, READ_ERR_MSG.len() as u32);
+        self.syscall();
+        self.mov_imm32(EAX, SYS_EXIT);
+        self.mov_imm32(EDI, 1);
+        self.syscall();
+
+        self.place(message);
+        self.emit(READ_ERR_MSG);
+    }
+
+    /// fills in every jump and address now that the code is done
+    fn link(&mut self) {
+        let offset = |labels: &[Option<usize>], label: Label| {
+            labels[label].expect("every label is placed before linking")
+        };
+
+        for &(at, label) in self.jumps.iter() {
+            // jumps are relative to the end of the instruction, which is where
+            // the offset ends
+            let relative = offset(&self.labels, label) as i64 - (at + 4) as i64;
+            self.code[at..at + 4].copy_from_slice(&(relative as i32).to_le_bytes());
+        }
+
+        let layout = Layout::new(self.code.len());
+        let tape_len = self.options.tape_policy.fixed_len() * self.options.cell_width.bytes();
+        for &(at, address) in self.addresses.iter() {
+            let address = match address {
+                Address::Tape(offset) => layout.data + offset,
+                Address::Input => layout.data + tape_len as u64,
+                Address::Code(label) => layout.code + offset(&self.labels, label) as u64,
+            };
+            self.code[at..at + 8].copy_from_slice(&address.to_le_bytes());
+        }
+    }
+}
+
+impl<W, R> InstructionHandler<W, R> for MachineCode
+where
+    W: std::io::Write,
+    R: std::io::Read,
+{
+    fn add(
+        &mut self,
+        offset: isize,
+        value: i32,
+        _: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let value = self.options.cell_width.wrap(value);
+        if value != 0 {
+            self.cell(0x80, 0x81, 0, offset);
+            self.immediate(value);
+        }
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn move_ptr(
+        &mut self,
+        amount: isize,
+        _: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        self.move_by(amount);
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn write_ptr(
+        &mut self,
+        offset: isize,
+        count: usize,
+        _: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        self.has_write = true;
+        // lea rsi, cell
+        self.emit(&[0x48, 0x8d]);
+        self.memory(ESI, offset);
+        for _ in 0..count {
+            self.jump(&[0xe8], self.write);
+        }
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn read_ptr(
+        &mut self,
+        offset: isize,
+        count: usize,
+        _: &Program,
+        _: &mut W,
+        _: &mut R,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        self.has_read = true;
+        self.load(offset);
+        for _ in 0..count {
+            self.jump(&[0xe8], self.read);
+        }
+        // mov cell, accumulator
+        self.cell(0x88, 0x89, EAX, offset);
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn set_zero(
+        &mut self,
+        offset: isize,
+        _: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        self.cell(0xc6, 0xc7, 0, offset);
+        self.immediate(0);
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn mul_add(
+        &mut self,
+        offset: isize,
+        factor: i32,
+        _: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        self.load(0);
+        match factor {
+            1 => {}
+            // neg eax
+            -1 => self.emit(&[0xf7, 0xd8]),
+            // imul eax, eax, factor
+            factor => {
+                self.emit(&[0x69, 0xc0]);
+                self.imm32(factor as u32);
+            }
+        }
+        // add cell, accumulator
+        self.cell(0x00, 0x01, EAX, offset);
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn scan(
+        &mut self,
+        step: isize,
+        _: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let start = self.label();
+        let end = self.label();
+        self.place(start);
+        self.compare_to_zero();
+        self.jump(&[0x0f, 0x84], end);
+        self.move_by(step);
+        self.jump(&[0xe9], start);
+        self.place(end);
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn jump_if_zero(
+        &mut self,
+        _: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let body = self.label();
+        let end = self.label();
+        self.loops.open((body, end));
+        self.compare_to_zero();
+        self.jump(&[0x0f, 0x84], end);
+        self.place(body);
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn jump_unless_zero(
+        &mut self,
+        _: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let (body, end) = self.loops.close(*instruction_ptr)?;
+        self.compare_to_zero();
+        self.jump(&[0x0f, 0x85], body);
+        self.place(end);
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn dump(&mut self, _: &Program, _: &mut W, instruction_ptr: &mut usize) -> Result<(), Error> {
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self, writer: &mut W) -> Result<(), Error> {
+        // exit with a status of 0
+        self.mov_imm32(EAX, SYS_EXIT);
+        self.emit(&[0x31, 0xff]);
+        self.syscall();
+
+        if self.has_write {
+            self.write_routine();
+        }
+        if self.has_read {
+            self.read_routine();
+        }
+        self.link();
+
+        // the tape, followed by the byte reads go through
+        let data_len = self.options.tape_policy.fixed_len() * self.options.cell_width.bytes() + 1;
+        elf::write(writer, &self.code, data_len)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_bytes;
+
+    #[test]
+    fn writes_an_elf_executable() {
+        let executable = generate_bytes("+[>+<-]", |_| MachineCode::new(Options::default()));
+        assert_eq!(&executable[..4], b"\x7fELF");
+        // the entry point is the first byte after the headers
+        let entry = u64::from_le_bytes(executable[24..32].try_into().unwrap());
+        assert_eq!(entry, Layout::new(0).code);
+    }
+
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    #[test]
+    fn executables_run() {
+        use std::io::Write;
+        use std::os::unix::fs::PermissionsExt;
+
+        let source = ",[.,]++++++++[>++++++++<-]>+.";
+        let options = Options {
+            cell_width: CellWidth::U16,
+            eof_policy: EofPolicy::Zero,
+            ..Default::default()
+        };
+
+        let path = std::env::temp_dir().join(format!("bfck-elf-{}", std::process::id()));
+        std::fs::write(&path, generate_bytes(source, |_| MachineCode::new(options))).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut child = std::process::Command::new(&path)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(b"hi").unwrap();
+        let output = child.wait_with_output().unwrap();
+        _ = std::fs::remove_file(&path);
+
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"hiA");
+    }
+}
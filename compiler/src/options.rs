@@ -0,0 +1,27 @@
+use parser::cell::CellWidth;
+use parser::eof::EofPolicy;
+use parser::tape::TapePolicy;
+
+use crate::arch::Arch;
+use crate::target::Target;
+
+/// everything that changes how a program is compiled
+///
+/// compiled programs always have a tape of `tape_policy.fixed_len()` cells,
+/// with the data pointer starting at `tape_policy.origin()`, which is in the
+/// middle of the tape whenever moving left is allowed, so programs that stay
+/// within that many cells behave like they do on the interpreter
+///
+/// moving out of the tape is not checked though, so a growing tape never grows,
+/// a wrapping tape never wraps, going past either end is undefined behavior
+/// instead of an error, and neither is moving left of the origin when
+/// `allow_left` is off
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    pub cell_width: CellWidth,
+    pub eof_policy: EofPolicy,
+    pub tape_policy: TapePolicy,
+    pub target: Target,
+    /// the architecture of native targets, other targets are portable
+    pub arch: Arch,
+}
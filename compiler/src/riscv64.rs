@@ -0,0 +1,232 @@
+use parser::cell::CellWidth;
+use parser::eof::EofPolicy;
+
+use crate::gnu_as::Isa;
+
+use std::io::Write;
+
+/// `{tape_size}` must be replaced by the size of the tape in bytes
+///
+/// the data pointer lives in `s1`, `ecall` only ever changes `a0`
+///
+/// GNU ld relaxes loads of addresses near `__global_pointer$` into accesses
+/// relative to `gp`, and the tape at the start of .bss is right there, so `gp`
+/// is set up first, without relaxing, or that load would be relaxed too
+static PRELUDE: &str = r#"    .global _start
+
+    .bss
+tape:
+    .skip {tape_size}
+input:
+    .skip 1
+
+    .section .rodata
+read_err_msg:
+    .ascii "failed to read from input\n"
+read_err_end:
+
+    .text
+_start:
+    .option push
+    .option norelax
+    la gp, __global_pointer$
+    .option pop
+    la s1, tape
+"#;
+
+static EXIT: &str = r#"    li a0, 0
+    li a7, 93
+    ecall"#;
+
+/// `a1` must point to the cell to write
+static WRITE: &str = r#"_w:
+    li a0, 1
+    li a2, 1
+    li a7, 64
+    ecall
+    ret"#;
+
+/// the previous value of the cell is kept in `t3` for `{eof}`
+static READ: &str = r#"_rd:
+    mv t3, a0
+    li a0, 0
+    la a1, input
+    li a2, 1
+    li a7, 63
+    ecall
+    bltz a0, _f
+    beqz a0, _rz
+    lbu a0, 0(a1)
+    ret
+_rz:
+{eof}
+    ret
+_f:
+    li a0, 2
+    la a1, read_err_msg
+    la a2, read_err_end
+    sub a2, a2, a1
+    li a7, 64
+    ecall
+    li a0, 1
+    li a7, 93
+    ecall"#;
+
+/// RV64 linux, needs the M extension for multiplication
+///
+/// conditional branches only reach 4 KiB away, `j` reaches 1 MiB away
+#[derive(Debug, Default)]
+pub struct Riscv64;
+
+impl Isa for Riscv64 {
+    const PRELUDE: &'static str = PRELUDE;
+    const WRITE: &'static str = WRITE;
+    const READ: &'static str = READ;
+    const EXIT: &'static str = EXIT;
+
+    const DATA_PTR: &'static str = "s1";
+    const CELL: &'static str = "a0";
+    const OTHER: &'static str = "a2";
+    const FACTOR: &'static str = "t2";
+    const WRITE_ARG: &'static str = "a1";
+    const ZERO: &'static str = "zero";
+
+    fn eof(policy: EofPolicy) -> &'static str {
+        match policy {
+            EofPolicy::Unchanged => "    mv a0, t3",
+            EofPolicy::Zero => "    li a0, 0",
+            EofPolicy::MinusOne => "    li a0, -1",
+        }
+    }
+
+    fn constant<W>(writer: &mut W, reg: &str, value: i64) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "    li {}, {}", reg, value)
+    }
+
+    /// immediates of `addi` only have 12 bits, so bigger values go through
+    /// `t0` first
+    fn add_immediate<W>(writer: &mut W, dest: &str, src: &str, value: i64) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        match value {
+            0 if dest == src => Ok(()),
+            -2048..=2047 => writeln!(writer, "    addi {}, {}, {}", dest, src, value),
+            value => {
+                Riscv64::constant(writer, "t0", value)?;
+                writeln!(writer, "    add {}, {}, t0", dest, src)
+            }
+        }
+    }
+
+    /// loads and stores take a 12-bit offset, so further cells have their
+    /// address put in `t1`
+    fn address<W>(writer: &mut W, offset: isize) -> std::io::Result<String>
+    where
+        W: Write,
+    {
+        match offset {
+            -2048..=2047 => Ok(format!("{}(s1)", offset)),
+            offset => {
+                Riscv64::constant(writer, "t1", offset as i64)?;
+                writeln!(writer, "    add t1, s1, t1")?;
+                Ok("0(t1)".to_string())
+            }
+        }
+    }
+
+    fn load<W>(writer: &mut W, width: CellWidth, reg: &str, address: &str) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        let load = match width {
+            CellWidth::U8 => "lbu",
+            CellWidth::U16 => "lhu",
+            CellWidth::U32 => "lwu",
+        };
+        writeln!(writer, "    {} {}, {}", load, reg, address)
+    }
+
+    fn store<W>(writer: &mut W, width: CellWidth, reg: &str, address: &str) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        let store = match width {
+            CellWidth::U8 => "sb",
+            CellWidth::U16 => "sh",
+            CellWidth::U32 => "sw",
+        };
+        writeln!(writer, "    {} {}, {}", store, reg, address)
+    }
+
+    fn branch_if_zero<W>(writer: &mut W, reg: &str, label: &str) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "    beqz {}, {}", reg, label)
+    }
+
+    fn branch_unless_zero<W>(writer: &mut W, reg: &str, label: &str) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "    bnez {}, {}", reg, label)
+    }
+
+    fn jump<W>(writer: &mut W, label: &str) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "    j {}", label)
+    }
+
+    fn call<W>(writer: &mut W, label: &str) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "    call {}", label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_text;
+    use crate::gnu_as::GnuAs;
+    use crate::options::Options;
+
+    #[test]
+    fn loops_branch_over_jumps() {
+        let assembly = generate_text("+[.>]", |writer| {
+            GnuAs::<Riscv64>::new(writer, Options::default()).unwrap()
+        });
+        assert!(assembly.contains("    bnez a0, _j0b\n    j _j0e\n_j0b:\n"));
+        assert!(assembly.contains("    beqz a0, _j0e\n    j _j0b\n_j0e:\n"));
+    }
+
+    #[test]
+    fn eof_policy_is_applied_on_end_of_input() {
+        let options = Options {
+            cell_width: CellWidth::U16,
+            eof_policy: EofPolicy::MinusOne,
+            ..Default::default()
+        };
+        let assembly = generate_text(",", |writer| {
+            GnuAs::<Riscv64>::new(writer, options).unwrap()
+        });
+        assert!(assembly.contains("    call _rd\n    sh a0, 0(s1)\n"));
+        assert!(assembly.contains("_rz:\n    li a0, -1\n    ret\n"));
+    }
+
+    #[test]
+    fn gp_is_set_up_without_relaxing() {
+        let assembly = generate_text("+", |writer| {
+            GnuAs::<Riscv64>::new(writer, Options::default()).unwrap()
+        });
+        // without gp, GNU ld could relax the load of the tape into garbage
+        assert!(assembly.contains("    .option norelax\n    la gp, __global_pointer$\n"));
+    }
+}
@@ -0,0 +1,40 @@
+/// what the compiler turns programs into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Target {
+    /// linux executables, or assembly when assembling, for the architecture
+    /// in the options
+    #[default]
+    Native,
+    /// a single C source file, which any C compiler can build
+    C,
+    /// a WebAssembly module in the text format, which does io through
+    /// functions imported from the host
+    Wat,
+    /// textual LLVM IR, which can be optimized and built by clang or llc
+    Llvm,
+}
+
+impl TryFrom<&str> for Target {
+    type Error = &'static str;
+
+    fn try_from(value: &str) -> Result<Target, Self::Error> {
+        match value {
+            "native" => Ok(Target::Native),
+            "c" => Ok(Target::C),
+            "wat" => Ok(Target::Wat),
+            "llvm" => Ok(Target::Llvm),
+            _ => Err("target must be one of native, c, wat or llvm"),
+        }
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Target::Native => write!(f, "native"),
+            Target::C => write!(f, "c"),
+            Target::Wat => write!(f, "wat"),
+            Target::Llvm => write!(f, "llvm"),
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use parser::cell::CellWidth;
+use parser::eof::EofPolicy;
+use parser::error::Error;
+use parser::instruction_handler::InstructionHandler;
+use parser::program::Program;
+
+use crate::loops::OpenLoops;
+use crate::options::Options;
+
+/// the size of a page of WebAssembly memory
+const PAGE_SIZE: usize = 65536;
+
+/// `{pages}` must be replaced by how many pages of memory the tape takes, and
+/// `{origin}` by the address of the cell the data pointer starts at
+///
+/// the host provides `read`, which returns the next byte of input, or -1 on the
+/// end of input, and `write`, which outputs the lowest byte of its argument,
+/// the tape is the exported memory, and the program runs when `main` is called
+static PRELUDE: &str = r#"(module
+  (import "env" "read" (func $read (result i32)))
+  (import "env" "write" (func $write (param i32)))
+  (memory (export "memory") {pages})
+  (func (export "main") (local $p i32) (local $c i32)
+    (local.set $p (i32.const {origin}))
+"#;
+
+/// compiles programs into a WebAssembly module in the text format, with the
+/// tape in linear memory and the data pointer in the `$p` local, which holds
+/// the address of the current cell
+///
+/// loops check the current cell before running at all, and again at the end
+/// of every iteration, like the native targets do
+#[derive(Debug, Default)]
+pub struct Wat {
+    options: Options,
+    loops: OpenLoops<usize>,
+}
+
+impl Wat {
+    pub fn new<W>(writer: &mut W, options: Options) -> std::io::Result<Wat>
+    where
+        W: std::io::Write,
+    {
+        let tape_len = options.tape_policy.fixed_len() * options.cell_width.bytes();
+        let origin = options.tape_policy.origin() * options.cell_width.bytes();
+        let prelude = PRELUDE
+            .replace("{pages}", &tape_len.div_ceil(PAGE_SIZE).max(1).to_string())
+            .replace("{origin}", &origin.to_string());
+        write!(writer, "{}", prelude)?;
+        Ok(Wat {
+            options,
+            ..Default::default()
+        })
+    }
+
+    /// every loop nests two more levels, `loop` inside of `if`
+    fn indent(&self) -> String {
+        " ".repeat(4 + self.loops.depth() * 6)
+    }
+
+    /// the address of the cell `offset` cells away from the data pointer
+    fn address(&self, offset: isize) -> String {
+        let offset = offset * self.options.cell_width.bytes() as isize;
+        match offset {
+            0 => "(local.get $p)".to_string(),
+            offset => format!("(i32.add (local.get $p) (i32.const {}))", offset),
+        }
+    }
+
+    /// the value of the cell `offset` cells away from the data pointer
+    fn load(&self, offset: isize) -> String {
+        let load = match self.options.cell_width {
+            CellWidth::U8 => "i32.load8_u",
+            CellWidth::U16 => "i32.load16_u",
+            CellWidth::U32 => "i32.load",
+        };
+        format!("({} {})", load, self.address(offset))
+    }
+
+    /// stores `value` into the cell `offset` cells away from the data pointer,
+    /// storing into narrower cells keeps only the lowest bits, which is how
+    /// cells wrap around
+    fn store(&self, offset: isize, value: &str) -> String {
+        let store = match self.options.cell_width {
+            CellWidth::U8 => "i32.store8",
+            CellWidth::U16 => "i32.store16",
+            CellWidth::U32 => "i32.store",
+        };
+        format!("({} {} {})", store, self.address(offset), value)
+    }
+
+    fn line<W>(&self, writer: &mut W, line: &str) -> Result<(), Error>
+    where
+        W: std::io::Write,
+    {
+        writeln!(writer, "{}{}", self.indent(), line)?;
+        Ok(())
+    }
+}
+
+impl<W, R> InstructionHandler<W, R> for Wat
+where
+    W: std::io::Write,
+    R: std::io::Read,
+{
+    fn add(
+        &mut self,
+        offset: isize,
+        value: i32,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let value = self.options.cell_width.wrap(value);
+        if value != 0 {
+            let sum = format!("(i32.add {} (i32.const {}))", self.load(offset), value);
+            self.line(writer, &self.store(offset, &sum))?;
+        }
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn move_ptr(
+        &mut self,
+        amount: isize,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        self.line(writer, &format!("(local.set $p {})", self.address(amount)))?;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn write_ptr(
+        &mut self,
+        offset: isize,
+        count: usize,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        for _ in 0..count {
+            self.line(writer, &format!("(call $write {})", self.load(offset)))?;
+        }
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn read_ptr(
+        &mut self,
+        offset: isize,
+        count: usize,
+        _: &Program,
+        writer: &mut W,
+        _: &mut R,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let read = self.store(offset, "(local.get $c)");
+        let eof = match self.options.eof_policy {
+            EofPolicy::Unchanged => String::new(),
+            EofPolicy::Zero => format!(" (else {})", self.store(offset, "(i32.const 0)")),
+            EofPolicy::MinusOne => format!(" (else {})", self.store(offset, "(i32.const -1)")),
+        };
+        for _ in 0..count {
+            self.line(writer, "(local.set $c (call $read))")?;
+            self.line(
+                writer,
+                &format!(
+                    "(if (i32.ge_s (local.get $c) (i32.const 0)) (then {}){})",
+                    read, eof
+                ),
+            )?;
+        }
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn set_zero(
+        &mut self,
+        offset: isize,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        self.line(writer, &self.store(offset, "(i32.const 0)"))?;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn mul_add(
+        &mut self,
+        offset: isize,
+        factor: i32,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let product = format!("(i32.mul {} (i32.const {}))", self.load(0), factor);
+        let sum = format!("(i32.add {} {})", self.load(offset), product);
+        self.line(writer, &self.store(offset, &sum))?;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn scan(
+        &mut self,
+        step: isize,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let scan = format!(
+            "(block $s (loop $l (br_if $s (i32.eqz {})) (local.set $p {}) (br $l)))",
+            self.load(0),
+            self.address(step)
+        );
+        self.line(writer, &scan)?;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn jump_if_zero(
+        &mut self,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let jump = self.loops.opened();
+        self.line(writer, &format!("(if {}", self.load(0)))?;
+        self.line(writer, "  (then")?;
+        self.line(writer, &format!("    (loop $j{}", jump))?;
+        self.loops.open(jump);
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn jump_unless_zero(
+        &mut self,
+        _: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let jump = self.loops.close(*instruction_ptr)?;
+        self.line(
+            writer,
+            &format!("      (br_if $j{} {}))))", jump, self.load(0)),
+        )?;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn dump(&mut self, _: &Program, _: &mut W, instruction_ptr: &mut usize) -> Result<(), Error> {
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self, writer: &mut W) -> Result<(), Error> {
+        writeln!(writer, "  )")?;
+        writeln!(writer, ")")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_text;
+
+    #[test]
+    fn tape_is_linear_memory() {
+        let options = Options {
+            cell_width: CellWidth::U32,
+            ..Default::default()
+        };
+        let module = generate_text("+[-]", |writer| Wat::new(writer, options).unwrap());
+        // 30000 cells of 4 bytes take 2 pages
+        assert!(module.contains("(memory (export \"memory\") 2)"));
+        assert!(module.contains("(i32.store (local.get $p) (i32.const 0))"));
+    }
+
+    #[test]
+    fn loops_check_before_and_after() {
+        let module = generate_text("+[.>]", |writer| {
+            Wat::new(writer, Options::default()).unwrap()
+        });
+        assert!(module
+            .contains("    (if (i32.load8_u (local.get $p))\n      (then\n        (loop $j0\n"));
+        assert!(module.contains("          (br_if $j0 (i32.load8_u (local.get $p))))))\n"));
+        assert_eq!(module.matches('(').count(), module.matches(')').count());
+    }
+}
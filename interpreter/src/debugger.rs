@@ -0,0 +1,365 @@
+use crate::Interpreter;
+use parser::error::Error;
+use parser::ir::Op;
+use parser::program::Program;
+use parser::{Parser, Status};
+
+use std::io::{Read, Write};
+
+/// how many cells to each side of `data_ptr` are shown when printing the tape
+static TAPE_RADIUS: usize = 5;
+
+static HELP: &str = "\
+commands:
+  step, s [n]          run the next instruction, or the next n instructions
+  next, n              like step, but runs entire loops at once
+  continue, c          run until a breakpoint is reached or the program ends
+  break, b <index>     stop before running the instruction at index
+  break, b line <n>    stop before running the first instruction on line n
+  delete, d <index>    remove the breakpoint at index
+  breakpoints          list every breakpoint
+  tape, t [radius]     show the cells around the data pointer
+  where, w             show the instruction about to run
+  help, h              show this message
+  quit, q              stop debugging";
+
+/// runs a program through the `Interpreter` a little at a time, so it can be
+/// inspected in between instructions
+///
+/// the program is expected to be unoptimized, so every instruction maps back to
+/// the source code it came from
+#[derive(Debug)]
+pub struct Debugger<W, R>
+where
+    W: Write,
+    R: Read,
+{
+    parser: Parser<W, R, Interpreter>,
+}
+
+impl<W, R> Debugger<W, R>
+where
+    W: Write,
+    R: Read,
+{
+    pub fn new(program: Program, mut parser: Parser<W, R, Interpreter>) -> Debugger<W, R> {
+        parser.load(program);
+        Debugger { parser }
+    }
+
+    pub fn parser(&self) -> &Parser<W, R, Interpreter> {
+        &self.parser
+    }
+
+    pub fn program(&self) -> &Program {
+        self.parser.program()
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.parser.breakpoints()
+    }
+
+    /// adds a breakpoint to the instruction at `index`, returning false when
+    /// there is no such instruction
+    pub fn add_breakpoint(&mut self, index: usize) -> bool {
+        self.parser.add_breakpoint(index)
+    }
+
+    /// adds a breakpoint to the first instruction on `line` of the source code,
+    /// returning the index of that instruction, if there is one
+    pub fn add_line_breakpoint(&mut self, line: usize) -> Option<usize> {
+        let index = self
+            .program()
+            .spans()
+            .iter()
+            .position(|span| span.start.line == line)?;
+        self.parser.add_breakpoint(index);
+        Some(index)
+    }
+
+    /// removes the breakpoint at `index`, returning false when there was none
+    pub fn remove_breakpoint(&mut self, index: usize) -> bool {
+        self.parser.remove_breakpoint(index)
+    }
+
+    /// runs a single instruction
+    pub fn step(&mut self) -> Result<Status, Error> {
+        self.parser.run_for(1)
+    }
+
+    /// runs a single instruction, unless it is the start of a loop, in which
+    /// case the entire loop is run, stopping early only on breakpoints inside
+    /// of it
+    pub fn step_over(&mut self) -> Result<Status, Error> {
+        let start = self.parser.instruction_ptr();
+        let Some(Op::JumpIfZero) = self.program().ops().get(start) else {
+            return self.step();
+        };
+        let end = self
+            .program()
+            .jump_target(start)
+            .expect("programs always have balanced jumps");
+
+        loop {
+            let status = self.step()?;
+            if status != Status::Running || self.parser.instruction_ptr() == end + 1 {
+                return Ok(status);
+            }
+        }
+    }
+
+    /// runs until reaching a breakpoint or the end of the program
+    pub fn resume(&mut self) -> Result<Status, Error> {
+        self.parser.run_for(u64::MAX)
+    }
+
+    /// the cells `radius` cells to the left and right of `data_ptr`, along
+    /// with their position relative to where `data_ptr` started
+    pub fn tape(&self, radius: usize) -> Vec<(isize, u32)> {
+        self.parser.instruction_handler().window(radius)
+    }
+
+    /// describes where the program is stopped at
+    fn location(&self) -> String {
+        let instruction_ptr = self.parser.instruction_ptr();
+        match self.program().ops().get(instruction_ptr) {
+            Some(op) => format!(
+                "instruction {} `{}` at {}",
+                instruction_ptr,
+                op,
+                self.program().spans()[instruction_ptr]
+            ),
+            None => format!("program finished after {} steps", self.parser.steps()),
+        }
+    }
+
+    fn print_tape<U>(&self, radius: usize, ui: &mut U) -> std::io::Result<()>
+    where
+        U: Write,
+    {
+        let position = self.parser.instruction_handler().position();
+        let cells = self.tape(radius);
+
+        for (cell, _) in cells.iter() {
+            write!(ui, "{:>7}", cell)?;
+        }
+        writeln!(ui)?;
+        for (cell, value) in cells.iter() {
+            match *cell == position {
+                true => write!(ui, "{:>7}", format!("[{}]", value))?,
+                false => write!(ui, "{:>7}", value)?,
+            }
+        }
+        writeln!(ui)
+    }
+
+    fn print_status<U>(&self, status: Status, ui: &mut U) -> std::io::Result<()>
+    where
+        U: Write,
+    {
+        match status {
+            Status::Running | Status::Halted => writeln!(ui, "{}", self.location()),
+            Status::NeedsInput => writeln!(ui, "waiting for input, {}", self.location()),
+            Status::Breakpoint(_) => writeln!(ui, "breakpoint hit, {}", self.location()),
+        }
+    }
+
+    /// runs a single debugger command, returning false when the debugger should
+    /// stop
+    fn command<U>(&mut self, command: &str, ui: &mut U) -> Result<bool, Error>
+    where
+        U: Write,
+    {
+        let mut words = command.split_whitespace();
+        let Some(name) = words.next() else {
+            return Ok(true);
+        };
+        let argument = words.next();
+
+        match (name, argument) {
+            ("step" | "s", count) => {
+                let count = match count.map(str::parse::<usize>) {
+                    None => 1,
+                    Some(Ok(count)) => count,
+                    Some(Err(_)) => {
+                        writeln!(ui, "expected the amount of instructions to step")?;
+                        return Ok(true);
+                    }
+                };
+                let status = self.parser.run_for(count as u64)?;
+                self.print_status(status, ui)?;
+            }
+            ("next" | "n", _) => {
+                let status = self.step_over()?;
+                self.print_status(status, ui)?;
+            }
+            ("continue" | "c", _) => {
+                let status = self.resume()?;
+                self.print_status(status, ui)?;
+            }
+            ("break" | "b", Some("line")) => match words.next().map(str::parse::<usize>) {
+                Some(Ok(line)) => match self.add_line_breakpoint(line) {
+                    Some(index) => writeln!(ui, "breakpoint set at instruction {}", index)?,
+                    None => writeln!(ui, "there are no instructions on line {}", line)?,
+                },
+                _ => writeln!(ui, "expected a line number")?,
+            },
+            ("break" | "b", Some(index)) => match index.parse::<usize>() {
+                Ok(index) if self.add_breakpoint(index) => {
+                    writeln!(ui, "breakpoint set at instruction {}", index)?
+                }
+                Ok(index) => writeln!(ui, "there is no instruction {}", index)?,
+                Err(_) => writeln!(ui, "expected an instruction index")?,
+            },
+            ("delete" | "d", Some(index)) => match index.parse::<usize>() {
+                Ok(index) if self.remove_breakpoint(index) => {
+                    writeln!(ui, "breakpoint at instruction {} removed", index)?
+                }
+                Ok(index) => writeln!(ui, "there is no breakpoint at instruction {}", index)?,
+                Err(_) => writeln!(ui, "expected an instruction index")?,
+            },
+            ("breakpoints", _) => {
+                for index in self.breakpoints() {
+                    writeln!(
+                        ui,
+                        "instruction {} at {}",
+                        index,
+                        self.program().spans()[index]
+                    )?;
+                }
+            }
+            ("tape" | "t", radius) => match radius.map(str::parse::<usize>) {
+                None => self.print_tape(TAPE_RADIUS, ui)?,
+                Some(Ok(radius)) => self.print_tape(radius, ui)?,
+                Some(Err(_)) => writeln!(ui, "expected the amount of cells to show")?,
+            },
+            ("where" | "w", _) => writeln!(ui, "{}", self.location())?,
+            ("help" | "h", _) => writeln!(ui, "{}", HELP)?,
+            ("quit" | "q", _) => return Ok(false),
+            _ => writeln!(ui, "unknown command `{}`, try `help`", command.trim())?,
+        }
+
+        Ok(true)
+    }
+
+    /// runs every command from `commands` until one of them is `quit` or there
+    /// are no more commands, talking to the user through `ui`
+    ///
+    /// errors from the program itself, like going out of the tape, are shown
+    /// to the user instead of ending the session, so the state that caused
+    /// them can still be inspected
+    pub fn run<C, U>(&mut self, commands: C, ui: &mut U) -> Result<(), Error>
+    where
+        C: IntoIterator<Item = String>,
+        U: Write,
+    {
+        writeln!(ui, "{}", self.location())?;
+        write!(ui, "(bfck) ")?;
+        ui.flush()?;
+
+        for command in commands {
+            let result = self.command(&command, ui);
+            // the program output has to show up before whatever the debugger
+            // says about it
+            self.parser.writer_mut().flush()?;
+            match result {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) => writeln!(ui, "error: {}", e)?,
+            }
+            write!(ui, "(bfck) ")?;
+            ui.flush()?;
+        }
+
+        writeln!(ui)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::error::RuntimeError;
+    use parser::limits::Limits;
+
+    fn debugger(source: &str) -> Debugger<Vec<u8>, std::io::Empty> {
+        let program = Program::parse(source).unwrap();
+        let parser = Parser::new(vec![], std::io::empty(), Interpreter::new());
+        Debugger::new(program, parser)
+    }
+
+    #[test]
+    fn limits() {
+        let program = Program::parse("+[]").unwrap();
+        let parser = Parser::new(vec![], std::io::empty(), Interpreter::new())
+            .with_limits(Limits::default().with_fuel(100));
+        let mut debugger = Debugger::new(program, parser);
+
+        assert!(matches!(
+            debugger.resume(),
+            Err(Error::Runtime(RuntimeError::OutOfFuel { steps: 100, .. }))
+        ));
+        // running out of fuel also stops single steps
+        assert!(matches!(
+            debugger.step(),
+            Err(Error::Runtime(RuntimeError::OutOfFuel { .. }))
+        ));
+    }
+
+    #[test]
+    fn stepping() {
+        let mut debugger = debugger("++[->+<]>.");
+        assert_eq!(debugger.step().unwrap(), Status::Running);
+        assert_eq!(debugger.parser().instruction_ptr(), 1);
+
+        // stepping over the loop runs it entirely
+        assert_eq!(debugger.step_over().unwrap(), Status::Running);
+        assert_eq!(debugger.parser().instruction_ptr(), 7);
+        assert_eq!(debugger.tape(1), vec![(-1, 0), (0, 0), (1, 2)]);
+
+        assert_eq!(debugger.step().unwrap(), Status::Running);
+        assert_eq!(debugger.step().unwrap(), Status::Halted);
+        assert_eq!(debugger.parser().writer(), &[2]);
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut debugger = debugger("+++\n[->+<]\n>.");
+        assert_eq!(debugger.add_line_breakpoint(2), Some(1));
+        assert_eq!(debugger.add_line_breakpoint(4), None);
+        assert!(debugger.add_breakpoint(3));
+        assert!(!debugger.add_breakpoint(20));
+
+        assert_eq!(debugger.resume().unwrap(), Status::Breakpoint(1));
+        // breakpoints inside of loops stop stepping over them too
+        assert_eq!(debugger.step_over().unwrap(), Status::Breakpoint(3));
+        assert_eq!(debugger.resume().unwrap(), Status::Breakpoint(3));
+
+        assert!(debugger.remove_breakpoint(3));
+        assert_eq!(debugger.resume().unwrap(), Status::Halted);
+        assert_eq!(debugger.parser().writer(), &[3]);
+    }
+
+    #[test]
+    fn commands() {
+        let mut debugger = debugger("+[>+<-]>.");
+        let commands = ["b 2", "c", "t 1", "n", "bogus", "s 10", "q", "s"];
+        let mut ui = vec![];
+        debugger.run(commands.map(String::from), &mut ui).unwrap();
+
+        let ui = String::from_utf8(ui).unwrap();
+        assert_eq!(
+            ui,
+            "\
+instruction 0 `add 1 @0` at line 1, col 1
+(bfck) breakpoint set at instruction 2
+(bfck) breakpoint hit, instruction 2 `move 1` at line 1, col 3
+(bfck)      -1      0      1
+      0    [1]      0
+(bfck) instruction 3 `add 1 @0` at line 1, col 4
+(bfck) unknown command `bogus`, try `help`
+(bfck) program finished after 9 steps
+(bfck) \n"
+        );
+    }
+}
@@ -1,20 +1,46 @@
-use lexer::token::Token;
+use parser::cell::CellWidth;
+use parser::eof::EofPolicy;
+use parser::error::{Error, RuntimeError};
 use parser::instruction_handler::InstructionHandler;
+use parser::program::Program;
+use parser::tape::{Overflow, TapePolicy};
 
 use std::collections::VecDeque;
+use std::io::Write;
 
 static CAPACITY: usize = 32;
 static INCREMENT: usize = CAPACITY / 2;
+/// how many cells to each side of `data_ptr` are printed by `#`
+static DUMP_RADIUS: usize = 4;
 
 #[derive(Debug)]
 pub struct Interpreter {
     /// the pointer to the current cell that should be acted upon
     data_ptr: usize,
     /// the tape of available cells to perform operations, this is initialized as an
-    /// VecDeque of 0u8 with capacity `CAPACITY`, and we simulate an infinite tape by
+    /// VecDeque of 0 with capacity `CAPACITY`, and we simulate an infinite tape by
     /// increasing its capacity by `INCREMENT` everytime we attempt to move out of
     /// bounds
-    tape: VecDeque<u8>,
+    ///
+    /// cells are stored as wide as the widest `CellWidth`, and every operation
+    /// wraps its result to `cell_width`
+    tape: VecDeque<u32>,
+    /// how wide each cell on the tape is, 8 bits by default
+    cell_width: CellWidth,
+    /// what to do with the cell being read into when there is no more input
+    eof_policy: EofPolicy,
+    /// how big the tape can get and what happens at its edges
+    tape_policy: TapePolicy,
+    /// where the first cell the data pointer pointed to is on the `tape`, this
+    /// moves along with the cells whenever the tape grows to the left
+    origin: usize,
+    /// the leftmost and rightmost cells touched so far, relative to `origin`,
+    /// used to know how many cells of a growing tape are in use
+    lowest: isize,
+    highest: isize,
+    /// how many bytes the read being executed already got, when the reader
+    /// has no input yet, the read is paused halfway and resumed later on
+    reads_done: usize,
 }
 
 impl Default for Interpreter {
@@ -28,7 +54,83 @@ impl Interpreter {
         Interpreter {
             tape: vec![0; CAPACITY].into_iter().collect(),
             data_ptr: INCREMENT,
+            cell_width: CellWidth::default(),
+            eof_policy: EofPolicy::default(),
+            tape_policy: TapePolicy::default(),
+            origin: INCREMENT,
+            lowest: 0,
+            highest: 0,
+            reads_done: 0,
+        }
+    }
+
+    /// clears the tape and moves `data_ptr` back to where it started, as if
+    /// the interpreter was just created, but keeping its configuration
+    pub fn reset(&mut self) {
+        *self = Interpreter::new()
+            .with_cell_width(self.cell_width)
+            .with_eof_policy(self.eof_policy)
+            .with_tape_policy(self.tape_policy);
+    }
+
+    pub fn with_cell_width(mut self, cell_width: CellWidth) -> Interpreter {
+        self.cell_width = cell_width;
+        self
+    }
+
+    pub fn with_eof_policy(mut self, eof_policy: EofPolicy) -> Interpreter {
+        self.eof_policy = eof_policy;
+        self
+    }
+
+    /// tapes that don't grow are allocated all at once, so changing the policy
+    /// resets the tape
+    pub fn with_tape_policy(mut self, tape_policy: TapePolicy) -> Interpreter {
+        self.tape_policy = tape_policy;
+        if tape_policy.overflow != Overflow::Grow {
+            self.tape = vec![0; tape_policy.fixed_len()].into_iter().collect();
+            self.origin = tape_policy.origin();
+            self.data_ptr = self.origin;
         }
+        self
+    }
+
+    pub fn cell_width(&self) -> CellWidth {
+        self.cell_width
+    }
+
+    pub fn eof_policy(&self) -> EofPolicy {
+        self.eof_policy
+    }
+
+    pub fn tape_policy(&self) -> TapePolicy {
+        self.tape_policy
+    }
+
+    /// the cell `data_ptr` points to, relative to where it started, so cells to
+    /// the left of the starting cell are negative
+    pub fn position(&self) -> isize {
+        self.data_ptr as isize - self.origin as isize
+    }
+
+    /// the value of the cell at `position`, relative to where `data_ptr`
+    /// started, cells that were never reached are always 0
+    pub fn peek(&self, position: isize) -> u32 {
+        self.origin
+            .checked_add_signed(position)
+            .and_then(|cell| self.tape.get(cell))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// the cells `radius` cells to the left and right of `data_ptr`, along
+    /// with their position relative to where `data_ptr` started
+    pub fn window(&self, radius: usize) -> Vec<(isize, u32)> {
+        let position = self.position();
+        let radius = radius as isize;
+        (position - radius..=position + radius)
+            .map(|cell| (cell, self.peek(cell)))
+            .collect()
     }
 
     /// increments the current tape `amount` `INCREMENT` stops to the left
@@ -61,6 +163,7 @@ impl Interpreter {
         self.tape.extend(vec![0; total_increment]);
         self.tape.rotate_right(total_increment);
         self.data_ptr += total_increment;
+        self.origin += total_increment;
         assert_eq!(self.tape[self.data_ptr], data_ptr_points_to, "after incrementing the queue to the left, the data pointer ended up in a different cell");
     }
 
@@ -85,6 +188,71 @@ impl Interpreter {
         let increment = INCREMENT * amount;
         self.tape.extend(vec![0; increment]);
     }
+
+    /// the index on the `tape` of the cell `offset` cells away from `data_ptr`,
+    /// following the `tape_policy` for cells that are out of bounds, growing
+    /// tapes are increased by `INCREMENT` stops to simulate a "infinite" tape as
+    /// the specification suggests.
+    ///
+    /// growing to the left changes where `data_ptr` points to, so the index
+    /// must only be computed after growing
+    fn cell(
+        &mut self,
+        offset: isize,
+        program: &Program,
+        instruction_ptr: usize,
+    ) -> Result<usize, RuntimeError> {
+        let position = self.position() + offset;
+        let out_of_tape = RuntimeError::OutOfTape {
+            cell: position,
+            instruction_ptr,
+            span: program.spans()[instruction_ptr],
+        };
+
+        if position < 0 && !self.tape_policy.allow_left {
+            return Err(out_of_tape);
+        }
+
+        match self.tape_policy.overflow {
+            Overflow::Wrap => {
+                return Ok(position.rem_euclid(self.tape.len() as isize) as usize);
+            }
+            Overflow::Error => {
+                return self
+                    .origin
+                    .checked_add_signed(position)
+                    .filter(|cell| *cell < self.tape.len())
+                    .ok_or(out_of_tape);
+            }
+            Overflow::Grow => {}
+        }
+
+        let lowest = self.lowest.min(position);
+        let highest = self.highest.max(position);
+        if let Some(max_len) = self.tape_policy.max_len {
+            if highest.abs_diff(lowest) >= max_len.get() {
+                return Err(out_of_tape);
+            }
+        }
+        self.lowest = lowest;
+        self.highest = highest;
+
+        let distance = offset.unsigned_abs();
+
+        if offset < 0 && distance > self.data_ptr {
+            let difference = distance - self.data_ptr;
+            let amount_of_increments = difference.div_ceil(INCREMENT);
+            self.increment_left(amount_of_increments);
+        }
+
+        if offset > 0 && self.data_ptr + distance >= self.tape.len() {
+            let missing = self.data_ptr + distance - self.tape.len() + 1;
+            let amount_of_increments = missing.div_ceil(INCREMENT);
+            self.increment_right(amount_of_increments);
+        }
+
+        Ok(self.data_ptr.wrapping_add_signed(offset))
+    }
 }
 
 impl<W, R> InstructionHandler<W, R> for Interpreter
@@ -92,167 +260,252 @@ where
     W: std::io::Write,
     R: std::io::Read,
 {
-    /// move the `data_ptr` `count` cells to the left, account for out of bounds
-    /// by increasing the tape by `INCREMENT` stops to simulate a "infinite" tape
-    /// as the specification suggests.
-    fn move_ptr_left(&mut self, count: usize, _: &[Token], _: &mut W, instruction_ptr: &mut usize) {
-        if count > self.data_ptr {
-            let difference = count - self.data_ptr;
-            let amount_of_increments = difference.div_ceil(INCREMENT);
-            self.increment_left(amount_of_increments);
-        }
-        self.data_ptr -= count;
-        *instruction_ptr += 1;
-    }
-
-    /// move the `data_ptr` `count` cells to the right, account for out of bounds
-    /// by increasing the tape by `INCREMENT` stops to simulate a "infinite" tape
-    /// as the specification suggests.
-    fn move_ptr_right(
+    /// add `value` to the cell `offset` cells away from `data_ptr`, wrapping
+    /// when the value goes over the maximum of `cell_width` or below 0
+    fn add(
         &mut self,
-        count: usize,
-        _: &[Token],
+        offset: isize,
+        value: i32,
+        program: &Program,
         _: &mut W,
         instruction_ptr: &mut usize,
-    ) {
-        if self.data_ptr + count >= self.tape.len() {
-            let new_len = self.data_ptr + count - self.tape.len();
-            let new_len = usize::max(new_len, 1);
-            let amount_of_increments = new_len.div_ceil(INCREMENT);
-            self.increment_right(amount_of_increments);
-        }
-        self.data_ptr += count;
+    ) -> Result<(), Error> {
+        let cell = self.cell(offset, program, *instruction_ptr)?;
+        self.tape[cell] = self.tape[cell].wrapping_add(value as u32) & self.cell_width.mask();
         *instruction_ptr += 1;
+        Ok(())
     }
 
-    /// increment the current cell on the `tape` pointed by `data_ptr`, wrapping
-    /// when the value exceeds `u8::MAX`
-    fn increment_ptr(&mut self, count: usize, _: &[Token], _: &mut W, instruction_ptr: &mut usize) {
-        self.tape[self.data_ptr] = self.tape[self.data_ptr].wrapping_add(count as u8);
-        *instruction_ptr += 1;
-    }
-
-    /// decrement the current cell on the `tape` pointed by `data_ptr`, wrapping
-    /// when the value would underflow below 0
-    fn decrement_ptr(&mut self, count: usize, _: &[Token], _: &mut W, instruction_ptr: &mut usize) {
-        self.tape[self.data_ptr] = self.tape[self.data_ptr].wrapping_sub(count as u8);
+    /// move the `data_ptr` `amount` cells, to the left when negative and to the
+    /// right otherwise, following the `tape_policy` when moving out of bounds
+    fn move_ptr(
+        &mut self,
+        amount: isize,
+        program: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        self.data_ptr = self.cell(amount, program, *instruction_ptr)?;
         *instruction_ptr += 1;
+        Ok(())
     }
 
-    /// writes to stdout the contents of the current cell pointed by `data_ptr`
-    /// as a `char` `count` times.
+    /// writes to stdout the lowest byte of the cell `offset` cells away from
+    /// `data_ptr` `count` times.
     fn write_ptr(
         &mut self,
+        offset: isize,
         count: usize,
-        _: &[Token],
+        program: &Program,
         writer: &mut W,
         instruction_ptr: &mut usize,
-    ) {
+    ) -> Result<(), Error> {
+        let cell = self.cell(offset, program, *instruction_ptr)?;
         for _ in 0..count {
-            _ = write!(writer, "{}", self.tape[self.data_ptr] as char);
+            writer.write_all(&[self.tape[cell] as u8])?;
         }
         *instruction_ptr += 1;
+        Ok(())
     }
 
-    /// read from stdin one byte at a time and add the byte to the current pointed
-    /// cell
+    /// read from stdin one byte at a time and store the byte into the cell
+    /// `offset` cells away from `data_ptr`, when there is nothing else to read
+    /// the cell is updated according to the `eof_policy`
     fn read_ptr(
         &mut self,
+        offset: isize,
         count: usize,
-        _: &[Token],
+        program: &Program,
         _: &mut W,
         reader: &mut R,
         instruction_ptr: &mut usize,
-    ) {
-        for _ in 0..count {
+    ) -> Result<(), Error> {
+        let cell = self.cell(offset, program, *instruction_ptr)?;
+        for _ in self.reads_done..count {
             let mut byte = [0u8; 1];
 
             match reader.read_exact(&mut byte) {
                 Ok(_) => {
-                    self.tape[self.data_ptr] = byte[0];
+                    self.tape[cell] = byte[0] as u32;
+                    self.reads_done += 1;
                 }
                 Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                    panic!("unexpected end of input");
+                    match self.eof_policy {
+                        EofPolicy::Unchanged => {}
+                        EofPolicy::Zero => self.tape[cell] = 0,
+                        EofPolicy::MinusOne => self.tape[cell] = self.cell_width.mask(),
+                    }
+                    break;
                 }
+                // the read is going to be tried again once there is input, so
+                // we keep track of how many bytes were already read
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Err(e.into()),
                 Err(e) => {
-                    panic!("failed to read from reader: {:?}", e);
+                    self.reads_done = 0;
+                    return Err(e.into());
                 }
             }
         }
+        self.reads_done = 0;
         *instruction_ptr += 1;
+        Ok(())
     }
 
-    /// if the current cell pointed by `data_ptr` is equals to 0, we should jump
-    /// to the next instruction after the matching closing jump, denoted by
-    /// `JumpUnlessZero` if the current cell is not zero, then we just skip to the
-    /// next instruction
+    /// sets the cell `offset` cells away from `data_ptr` to 0
+    fn set_zero(
+        &mut self,
+        offset: isize,
+        program: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let cell = self.cell(offset, program, *instruction_ptr)?;
+        self.tape[cell] = 0;
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    /// adds the current cell multiplied by `factor` into the cell `offset`
+    /// cells away from `data_ptr`, wrapping just like `add` does
     ///
-    /// since we accept a `count`, when cell is non-zero, we can skip all of the
-    /// next repeated occurrences directly to the next non-repeated token
-    fn jump_if_zero(&mut self, _: usize, tokens: &[Token], _: &mut W, instruction_ptr: &mut usize) {
-        let curr_val = self.tape[self.data_ptr];
-        if curr_val != 0 {
+    /// when the current cell is zero the loop this came from never runs, so the
+    /// other cell is left alone, even if it is outside of the tape
+    fn mul_add(
+        &mut self,
+        offset: isize,
+        factor: i32,
+        program: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        if self.tape[self.data_ptr] == 0 {
             *instruction_ptr += 1;
-            return;
+            return Ok(());
         }
-        let mut open_jumps = 1;
-        let mut index = *instruction_ptr + 1;
-        while open_jumps != 0 {
-            if index >= tokens.len() {
-                break;
-            }
 
-            match tokens[index] {
-                Token::JumpIfZero(count) => open_jumps += count,
-                Token::JumpUnlessZero(count) => open_jumps -= count,
-                _ => {}
+        let cell = self.cell(offset, program, *instruction_ptr)?;
+        let value = self.tape[self.data_ptr].wrapping_mul(factor as u32);
+        self.tape[cell] = self.tape[cell].wrapping_add(value) & self.cell_width.mask();
+        *instruction_ptr += 1;
+        Ok(())
+    }
+
+    /// moves `data_ptr` `step` cells at a time until it points to a zero cell
+    ///
+    /// a wrapping tape with no zero cells would be scanned forever, so a scan
+    /// stops after going over as many cells as there are on the tape, and is
+    /// picked up again on the next step, that way fuel and timeouts stop it
+    /// just like they stop the loop it was optimized from
+    fn scan(
+        &mut self,
+        step: isize,
+        program: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let mut moves = 0;
+        while self.tape[self.data_ptr] != 0 {
+            if moves == self.tape.len() {
+                return Ok(());
             }
+            self.data_ptr = self.cell(step, program, *instruction_ptr)?;
+            moves += 1;
+        }
+        *instruction_ptr += 1;
+        Ok(())
+    }
 
-            index += 1;
+    /// if the current cell pointed by `data_ptr` is equals to 0, we should jump
+    /// to the next instruction after the matching closing jump, denoted by
+    /// `JumpUnlessZero` if the current cell is not zero, then we just skip to the
+    /// next instruction
+    ///
+    /// the program was already validated by the parser, so the matching jump
+    /// always exists in the jump table
+    fn jump_if_zero(
+        &mut self,
+        program: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        if self.tape[self.data_ptr] != 0 {
+            *instruction_ptr += 1;
+            return Ok(());
         }
 
-        *instruction_ptr = index;
+        let target = program
+            .jump_target(*instruction_ptr)
+            .ok_or(RuntimeError::UnmatchedJump {
+                instruction_ptr: *instruction_ptr,
+            })?;
+        *instruction_ptr = target + 1;
+        Ok(())
     }
 
     /// if the current cell pointed by `data_ptr` is not equals to 0, we should
     /// jump to the next instruction after the matching opening jump, denoted by
     /// `JumpIfZero`; if the current cell is zero, then we just skip to the next
     /// instruction
-    ///
-    /// since we accept a `count`, when cell is zero, we can skip all of the
-    /// next repeated occurrences directly to the next non-repeated token
     fn jump_unless_zero(
         &mut self,
-        _: usize,
-        tokens: &[Token],
+        program: &Program,
         _: &mut W,
         instruction_ptr: &mut usize,
-    ) {
-        let curr_val = self.tape[self.data_ptr];
-        if curr_val == 0 {
+    ) -> Result<(), Error> {
+        if self.tape[self.data_ptr] == 0 {
             *instruction_ptr += 1;
-            return;
+            return Ok(());
         }
-        let mut open_jumps = 1;
-        let mut index = *instruction_ptr - 1;
-        while open_jumps != 0 {
-            match tokens[index] {
-                Token::JumpIfZero(count) => open_jumps -= count,
-                Token::JumpUnlessZero(count) => open_jumps += count,
-                _ => {}
-            }
 
-            index -= 1;
-        }
+        let target = program
+            .jump_target(*instruction_ptr)
+            .ok_or(RuntimeError::UnmatchedJump {
+                instruction_ptr: *instruction_ptr,
+            })?;
+        *instruction_ptr = target + 1;
+        Ok(())
+    }
 
-        *instruction_ptr = index + 1;
+    /// prints the cells around `data_ptr` to stderr, leaving the program
+    /// output alone, the current cell is the one between brackets
+    fn dump(
+        &mut self,
+        program: &Program,
+        _: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error> {
+        let position = self.position();
+        let cells = self
+            .window(DUMP_RADIUS)
+            .into_iter()
+            .map(|(cell, value)| match cell == position {
+                true => format!("[{}]", value),
+                false => value.to_string(),
+            })
+            .collect::<Vec<_>>();
+
+        let mut stderr = std::io::stderr().lock();
+        writeln!(
+            stderr,
+            "# at {}, cell {}: {}",
+            program.spans()[*instruction_ptr],
+            position,
+            cells.join(" ")
+        )?;
+        *instruction_ptr += 1;
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use parser::Parser;
+    use parser::input::Input;
+    use parser::limits::Limits;
+    use parser::program::Program;
+    use parser::tape::{Overflow, TapePolicy};
+    use parser::{Parser, Status};
+    use std::num::NonZeroUsize;
 
     #[derive(Debug, Default)]
     struct Writer {
@@ -277,8 +530,8 @@ mod tests {
         let writer = Writer::default();
         let interpreter = Interpreter::default();
         let mut parser = Parser::<Writer, R, Interpreter>::new(writer, reader, interpreter);
-        let tokens = lexer::Lexer::tokenize(input);
-        parser.interpret(tokens);
+        let program = Program::parse(input).unwrap();
+        parser.interpret(program).unwrap();
         parser
     }
 
@@ -306,6 +559,34 @@ mod tests {
         assert_eq!(parser.instruction_handler().tape.len(), 32);
     }
 
+    #[test]
+    fn wide_cells_wrap_at_their_width() {
+        let source = format!("{}>-", "+".repeat(256));
+
+        let parser = check(&source, std::io::Cursor::new(""));
+        assert_eq!(parser.instruction_handler().tape[16], 0);
+        assert_eq!(parser.instruction_handler().tape[17], 255);
+
+        for (cell_width, max) in [(CellWidth::U16, 65535), (CellWidth::U32, u32::MAX)] {
+            let program = Program::parse(&source).unwrap().optimize();
+            let interpreter = Interpreter::new().with_cell_width(cell_width);
+            let mut parser = Parser::new(Writer::default(), std::io::empty(), interpreter);
+            parser.interpret(program).unwrap();
+            assert_eq!(parser.instruction_handler().tape[16], 256);
+            assert_eq!(parser.instruction_handler().tape[17], max);
+        }
+    }
+
+    #[test]
+    fn wide_cells_write_their_lowest_byte() {
+        let source = format!("{}.", "+".repeat(256 + 65));
+        let program = Program::parse(&source).unwrap();
+        let interpreter = Interpreter::new().with_cell_width(CellWidth::U16);
+        let mut parser = Parser::new(Writer::default(), std::io::empty(), interpreter);
+        parser.interpret(program).unwrap();
+        assert_eq!(parser.writer().data, "A");
+    }
+
     #[test]
     fn write_chars() {
         let uppercase_a = (0..65).map(|_| "+").collect::<String>();
@@ -324,7 +605,7 @@ mod tests {
         let source = format!("[<<<<>[>>]]{uppercase_a}.");
         let parser = check(&source, std::io::Cursor::new(""));
         assert_eq!(parser.writer().data, "A");
-        assert_eq!(parser.instruction_ptr(), 8);
+        assert_eq!(parser.instruction_ptr(), 9);
 
         // we first set the current cell to any non-zero value, so we should skip
         // the first jump
@@ -332,12 +613,12 @@ mod tests {
         // we are skipping the all the jumps until the first non-jump instruction,
         // which should write "A" to the writer
         //
-        // then, we should hit the jump-unless-zero, which should also be skipped
+        // then, we should hit the jump-unless-zero's, which should also be skipped
         // and then just write "A" again
         let source = format!("+[[>{uppercase_a}.>]]<.");
         let parser = check(&source, std::io::Cursor::new(""));
         assert_eq!(parser.writer().data, "AA");
-        assert_eq!(parser.instruction_ptr(), 9);
+        assert_eq!(parser.instruction_ptr(), 11);
 
         // we first set the current cell to any non-zero value, so we skip the loop
         //
@@ -360,6 +641,18 @@ mod tests {
         assert_eq!(parser.instruction_ptr(), 6);
     }
 
+    #[test]
+    fn nested_jumps_closing_together() {
+        let source = "++[>+++[>++<-]<-]]";
+        assert!(Program::parse(source).is_err());
+
+        let source = "++[>+++[>++<-]]";
+        let parser = check(source, std::io::Cursor::new(""));
+        assert_eq!(parser.instruction_handler().tape[18], 6);
+        assert_eq!(parser.instruction_handler().tape[17], 0);
+        assert_eq!(parser.instruction_handler().tape[16], 2);
+    }
+
     #[test]
     fn hello_world() {
         let source = include_str!("../../samples/hello_world_pretty.bf");
@@ -373,6 +666,260 @@ mod tests {
         assert_eq!(parser.writer().data, "Hello World!\n");
     }
 
+    #[test]
+    fn optimized_programs_behave_the_same() {
+        let sources = [
+            include_str!("../../samples/hello_world_pretty.bf"),
+            include_str!("../../samples/hello_world_inline.bf"),
+            include_str!("../../samples/add_2_and_5.bf"),
+            "++++++++[>++++[>++>+++<<-]<-]>>[<<+>>-]<+[<]>>>.",
+        ];
+
+        for source in sources {
+            let expected = check(source, std::io::Cursor::new(""));
+
+            let program = Program::parse(source).unwrap().optimize();
+            let mut parser = Parser::new(Writer::default(), std::io::empty(), Interpreter::new());
+            parser.interpret(program).unwrap();
+
+            assert_eq!(parser.writer().data, expected.writer().data);
+            let handler = parser.instruction_handler();
+            let expected_handler = expected.instruction_handler();
+            assert_eq!(handler.tape, expected_handler.tape);
+            assert_eq!(handler.data_ptr, expected_handler.data_ptr);
+        }
+    }
+
+    #[test]
+    fn eof_policies() {
+        let source = include_str!("../../samples/rot_13.bf");
+        let parser = check(source, std::io::Cursor::new("Hello, World!"));
+        assert_eq!(parser.writer().data, "Uryyb, Jbeyq!");
+
+        let cases = [
+            (EofPolicy::Unchanged, CellWidth::U8, 7),
+            (EofPolicy::Zero, CellWidth::U8, 0),
+            (EofPolicy::MinusOne, CellWidth::U8, 255),
+            (EofPolicy::MinusOne, CellWidth::U16, 65535),
+        ];
+        for (eof_policy, cell_width, expected) in cases {
+            let program = Program::parse("+++++++,").unwrap();
+            let interpreter = Interpreter::new()
+                .with_cell_width(cell_width)
+                .with_eof_policy(eof_policy);
+            let mut parser = Parser::new(Writer::default(), std::io::empty(), interpreter);
+            parser.interpret(program).unwrap();
+            assert_eq!(parser.instruction_handler().tape[16], expected);
+        }
+    }
+
+    #[test]
+    fn failing_io_is_an_error() {
+        struct Failing;
+
+        impl std::io::Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("broken pipe"))
+            }
+        }
+
+        let program = Program::parse("+.,.").unwrap();
+        let mut parser = Parser::new(Writer::default(), Failing, Interpreter::new());
+        let result = parser.interpret(program);
+        assert!(matches!(result, Err(Error::Io(_))));
+        assert_eq!(parser.writer().data, "\u{1}");
+        assert_eq!(parser.instruction_ptr(), 2);
+    }
+
+    #[test]
+    fn tape_policies() {
+        let run = |source: &str, tape_policy: TapePolicy| {
+            let program = Program::parse(source).unwrap();
+            let interpreter = Interpreter::new().with_tape_policy(tape_policy);
+            let mut parser = Parser::new(Writer::default(), std::io::empty(), interpreter);
+            let result = parser.interpret(program);
+            (result, parser)
+        };
+
+        let bounded = TapePolicy {
+            max_len: NonZeroUsize::new(4),
+            ..TapePolicy::default()
+        };
+        let (result, parser) = run("+[>+]", bounded);
+        assert!(matches!(
+            result,
+            Err(Error::Runtime(RuntimeError::OutOfTape { cell: 4, .. }))
+        ));
+        assert_eq!(parser.instruction_ptr(), 2);
+
+        let (result, _) = run("<+>>+", bounded);
+        assert!(result.is_ok());
+        let (result, _) = run("<<+>>>>+", bounded);
+        assert!(matches!(
+            result,
+            Err(Error::Runtime(RuntimeError::OutOfTape { cell: 2, .. }))
+        ));
+
+        let no_left = TapePolicy {
+            allow_left: false,
+            ..TapePolicy::default()
+        };
+        let (result, _) = run("><", no_left);
+        assert!(result.is_ok());
+        let (result, _) = run("<", no_left);
+        assert!(matches!(
+            result,
+            Err(Error::Runtime(RuntimeError::OutOfTape { cell: -1, .. }))
+        ));
+
+        let wrapping = TapePolicy {
+            max_len: NonZeroUsize::new(4),
+            overflow: Overflow::Wrap,
+            ..TapePolicy::default()
+        };
+        let (result, parser) = run("<+>>++", wrapping);
+        assert!(result.is_ok());
+        assert_eq!(parser.instruction_handler().tape, [0, 2, 0, 1]);
+
+        let fixed = TapePolicy {
+            max_len: NonZeroUsize::new(4),
+            overflow: Overflow::Error,
+            ..TapePolicy::default()
+        };
+        let (result, parser) = run("<<+>>>+", fixed);
+        assert!(result.is_ok());
+        assert_eq!(parser.instruction_handler().tape, [1, 0, 0, 1]);
+        let (result, _) = run("<<<", fixed);
+        assert!(matches!(
+            result,
+            Err(Error::Runtime(RuntimeError::OutOfTape { cell: -3, .. }))
+        ));
+    }
+
+    #[test]
+    fn optimized_loops_that_never_run_stay_on_the_tape() {
+        let edge = TapePolicy {
+            max_len: NonZeroUsize::new(4),
+            allow_left: false,
+            overflow: Overflow::Error,
+        };
+
+        let run = |program: Program| {
+            let interpreter = Interpreter::new().with_tape_policy(edge);
+            let mut parser = Parser::new(Writer::default(), std::io::empty(), interpreter);
+            let result = parser.interpret(program);
+            (result, parser)
+        };
+
+        let program = Program::parse(">>>[->+<]+.").unwrap();
+        let (result, expected) = run(program.clone());
+        assert!(result.is_ok());
+        let (result, parser) = run(program.optimize());
+        assert!(result.is_ok());
+        assert_eq!(parser.writer().data, expected.writer().data);
+        assert_eq!(
+            parser.instruction_handler().tape,
+            expected.instruction_handler().tape
+        );
+    }
+
+    #[test]
+    fn resumable_execution() {
+        let program = Program::parse("+++[-],,[.,]").unwrap();
+        let interpreter = Interpreter::new().with_eof_policy(EofPolicy::Zero);
+        let mut parser = Parser::new(Writer::default(), Input::new(), interpreter);
+        parser.load(program);
+
+        assert_eq!(parser.run_for(3).unwrap(), Status::Running);
+        assert_eq!(parser.instruction_ptr(), 3);
+        assert!(parser.add_breakpoint(4));
+        assert_eq!(parser.run_for(100).unwrap(), Status::Breakpoint(4));
+
+        // the read is paused halfway until there is enough input for it
+        assert_eq!(parser.run_for(100).unwrap(), Status::NeedsInput);
+        parser.reader_mut().push(b"a");
+        assert_eq!(parser.run_for(100).unwrap(), Status::NeedsInput);
+        assert_eq!(parser.instruction_ptr(), 4);
+        parser.reader_mut().push(b"bc");
+        assert_eq!(parser.run_for(100).unwrap(), Status::NeedsInput);
+        assert_eq!(parser.writer().data, "bc");
+
+        parser.reader_mut().close();
+        assert_eq!(parser.run_for(100).unwrap(), Status::Halted);
+        assert_eq!(parser.step().unwrap(), Status::Halted);
+    }
+
+    #[test]
+    fn running_out_of_fuel() {
+        let program = Program::parse("+[]").unwrap();
+        let limits = Limits::default().with_fuel(100);
+        let mut parser = Parser::new(Writer::default(), std::io::empty(), Interpreter::new())
+            .with_limits(limits);
+        let result = parser.interpret(program);
+
+        let Err(Error::Runtime(RuntimeError::OutOfFuel {
+            steps,
+            instruction_ptr,
+            span,
+        })) = result
+        else {
+            panic!("expected to run out of fuel, got {:?}", result);
+        };
+        assert_eq!(steps, 100);
+        assert_eq!(parser.steps(), 100);
+        assert_eq!(instruction_ptr, parser.instruction_ptr());
+        assert_eq!(instruction_ptr, 2);
+        assert_eq!(span.start.column, 3);
+
+        // enough fuel to finish is not an error
+        let program = Program::parse("+++.").unwrap();
+        let limits = Limits::default().with_fuel(4);
+        let mut parser = Parser::new(Writer::default(), std::io::empty(), Interpreter::new())
+            .with_limits(limits);
+        parser.interpret(program).unwrap();
+        assert_eq!(parser.writer().data, "\u{3}");
+    }
+
+    #[test]
+    fn timing_out() {
+        let program = Program::parse("+[]").unwrap();
+        let limits = Limits::default().with_timeout(std::time::Duration::from_millis(10));
+        let mut parser = Parser::new(Writer::default(), std::io::empty(), Interpreter::new())
+            .with_limits(limits);
+        let result = parser.interpret(program);
+        assert!(matches!(
+            result,
+            Err(Error::Runtime(RuntimeError::TimedOut { .. }))
+        ));
+    }
+
+    #[test]
+    fn limits_stop_endless_scans() {
+        let wrapping = TapePolicy {
+            max_len: NonZeroUsize::new(2),
+            overflow: Overflow::Wrap,
+            ..TapePolicy::default()
+        };
+        let limits = [
+            Limits::default().with_fuel(100),
+            Limits::default().with_timeout(std::time::Duration::from_millis(10)),
+        ];
+
+        for limits in limits {
+            let program = Program::parse("+>+[>]").unwrap().optimize();
+            let interpreter = Interpreter::new().with_tape_policy(wrapping);
+            let mut parser =
+                Parser::new(Writer::default(), std::io::empty(), interpreter).with_limits(limits);
+            let result = parser.interpret(program);
+            assert!(matches!(
+                result,
+                Err(Error::Runtime(
+                    RuntimeError::OutOfFuel { .. } | RuntimeError::TimedOut { .. }
+                ))
+            ));
+        }
+    }
+
     #[test]
     fn add_two_and_five() {
         let source = include_str!("../../samples/add_2_and_5.bf");
@@ -1,3 +1,70 @@
+pub mod debugger;
 mod interpreter;
 
 pub use interpreter::Interpreter;
+
+use debugger::Debugger;
+
+use lexer::Extensions;
+use parser::error::Error;
+use parser::limits::Limits;
+use parser::program::Program;
+use std::io::Write;
+use std::path::Path;
+
+/// interprets the brainfuck file at `source` with the given `interpreter`, wired
+/// to the real stdin and stdout of the process, stopping early if the program
+/// goes over the given `limits`
+pub fn run<P>(
+    source: P,
+    interpreter: Interpreter,
+    limits: Limits,
+    extensions: Extensions,
+) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+{
+    let program = Program::from_file_with(source, extensions)?.optimize();
+
+    let stdout = std::io::stdout().lock();
+    let stdin = std::io::stdin().lock();
+    let mut parser = parser::Parser::new(stdout, stdin, interpreter).with_limits(limits);
+    let result = parser.interpret(program);
+    // whatever was written before stopping should still reach the user
+    parser.writer_mut().flush()?;
+    result?;
+
+    Ok(())
+}
+
+/// debugs the brainfuck file at `source` with the given `interpreter`, the
+/// debugger reads its commands from stdin and talks through stderr, while the
+/// program keeps writing to stdout and reads its input from stdin too, right
+/// after the command that made it read, going over the given `limits` stops
+/// the command that was running, but not the session
+pub fn debug<P>(
+    source: P,
+    interpreter: Interpreter,
+    limits: Limits,
+    extensions: Extensions,
+) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+{
+    // the program is not optimized so each instruction maps to a single token
+    let program = Program::from_file_with(source, extensions)?;
+    let parser =
+        parser::Parser::new(std::io::stdout(), std::io::stdin(), interpreter).with_limits(limits);
+
+    // stdin can't be locked for the whole session, as the program needs to
+    // read from it in between commands
+    let commands = std::iter::from_fn(|| {
+        let mut line = String::new();
+        match std::io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line),
+        }
+    });
+
+    Debugger::new(program, parser).run(commands, &mut std::io::stderr())
+}
@@ -1,3 +1,4 @@
+use crate::span::{Position, Span};
 use crate::token::Token;
 
 /// Lexer for the brainfuck source code, exposes only one method, namely `tokenize`.
@@ -28,6 +29,25 @@ use crate::token::Token;
 /// ```
 pub struct Lexer {}
 
+/// tokens that are not part of brainfuck, but are common enough to be supported
+/// when asked for, every extension is disabled by default so the bytes they use
+/// are still comments
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Extensions {
+    /// whether `#` is a `Token::Dump`
+    pub dump: bool,
+}
+
+impl Extensions {
+    /// the token `byte` represents with these extensions enabled, if any
+    fn token(&self, byte: u8) -> Option<Token> {
+        match byte {
+            b'#' if self.dump => Some(Token::Dump(1)),
+            byte => Token::try_from(byte).ok(),
+        }
+    }
+}
+
 impl Lexer {
     /// given a input slice of source code, Lexer will try to tokenize it ignoring all
     /// whitespaces and non-tokens and always give back a vector of tokens, which
@@ -36,34 +56,83 @@ impl Lexer {
     /// no validations are performed here purposefully, its not the responsibility of
     /// the lexer to validate if the source code is valid.
     pub fn tokenize(input: &str) -> Vec<Token> {
+        Lexer::tokenize_spanned(input)
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect()
+    }
+
+    /// the same as `tokenize`, but every token is paired with the `Span` of source
+    /// code it was collected from, which is what we need to point at a specific
+    /// place in the source when reporting errors or debugging
+    ///
+    /// ```rust
+    /// use lexer::Lexer;
+    /// use lexer::span::{Position, Span};
+    /// use lexer::token::Token;
+    ///
+    /// let tokens = Lexer::tokenize_spanned("+ +\n>");
+    /// assert_eq!(
+    ///     tokens,
+    ///     vec![
+    ///         (Token::Increment(2), Span::new(Position::new(0, 1, 1), Position::new(2, 1, 3))),
+    ///         (Token::MoveRight(1), Span::new(Position::new(4, 2, 1), Position::new(4, 2, 1))),
+    ///     ]
+    /// );
+    /// ```
+    pub fn tokenize_spanned(input: &str) -> Vec<(Token, Span)> {
+        Lexer::tokenize_with(input, Extensions::default())
+    }
+
+    /// the same as `tokenize_spanned`, but recognizing the tokens of the given
+    /// `Extensions` too
+    ///
+    /// ```rust
+    /// use lexer::token::Token;
+    /// use lexer::{Extensions, Lexer};
+    ///
+    /// let tokens = Lexer::tokenize_with("+#", Extensions { dump: true });
+    /// assert_eq!(tokens[1].0, Token::Dump(1));
+    /// assert_eq!(Lexer::tokenize_with("+#", Extensions::default()).len(), 1);
+    /// ```
+    pub fn tokenize_with(input: &str, extensions: Extensions) -> Vec<(Token, Span)> {
         let input = input.as_bytes();
         let mut tokens = vec![];
         let mut index = 0;
+        let mut position = Position::new(0, 1, 1);
 
         while index < input.len() {
-            let token = Token::try_from(input[index]);
+            let token = extensions.token(input[index]);
 
             // we couldn't convert the byte to a valid token, so we just consider it
             // a comment and skip over it
-            if token.is_err() {
+            if token.is_none() {
+                position.advance(input[index]);
                 index += 1;
                 continue;
             }
 
             // its obviously safe to unwrap here since we check above, but lets make
             // it clear this is intended!
-            assert!(token.is_ok(), "token was not valid yet we didn't skip it");
+            assert!(token.is_some(), "token was not valid yet we didn't skip it");
             let mut token = token.unwrap();
+            let start = position;
+            let mut end = position;
 
             // since we found a valid token, we will keep collecting tokens until we
             // find a different one
+            //
+            // jumps are the exception, every bracket needs its own matching pair,
+            // so we never group them together, and neither do we group dumps
+            position.advance(input[index]);
             index += 1;
-            while index < input.len() {
-                let next = Token::try_from(input[index]);
+            while index < input.len() && token.is_grouped() {
+                let next = extensions.token(input[index]);
 
                 // again, if the byte is a non-valid brainfuck token, we just skip
                 // over it
-                if next.is_err() {
+                if next.is_none() {
+                    position.advance(input[index]);
                     index += 1;
                     continue;
                 }
@@ -75,7 +144,7 @@ impl Lexer {
                 //
                 // its obviously safe to unwrap here since we check above, but lets make
                 // it clear this is intended!
-                assert!(next.is_ok(), "token was not valid yet we didn't skip it");
+                assert!(next.is_some(), "token was not valid yet we didn't skip it");
                 if next.unwrap().ne(&token) {
                     break;
                 }
@@ -84,10 +153,12 @@ impl Lexer {
                 // through the inner() helper and increase the index to we do the
                 // same with the next
                 *token.inner() += 1;
+                end = position;
+                position.advance(input[index]);
                 index += 1;
             }
 
-            tokens.push(token);
+            tokens.push((token, Span::new(start, end)));
         }
 
         tokens
@@ -126,4 +197,26 @@ mod tests {
     fn pretty_hello_world() {
         insta::assert_debug_snapshot!(check(include_str!("../../samples/hello_world_pretty.bf")));
     }
+
+    #[test]
+    fn jumps_are_never_grouped() {
+        insta::assert_debug_snapshot!(check("[[-]]"));
+    }
+
+    #[test]
+    fn spanned_runs_across_comments_and_lines() {
+        insta::assert_debug_snapshot!(Lexer::tokenize_spanned("++ comment\n+<<\n\n  ."));
+    }
+
+    #[test]
+    fn dump_is_opt_in() {
+        insta::assert_debug_snapshot!(Lexer::tokenize_with("+##+", Extensions { dump: true }));
+        assert_eq!(check("+##+"), vec![Token::Increment(2)]);
+    }
+
+    #[test]
+    fn spanned_columns_count_characters() {
+        let tokens = Lexer::tokenize_spanned("ção+");
+        assert_eq!(tokens[0].1.start, Position::new(5, 1, 4));
+    }
 }
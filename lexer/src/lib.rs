@@ -1,4 +1,5 @@
 mod lexer;
+pub mod span;
 pub mod token;
 
-pub use lexer::Lexer;
+pub use lexer::{Extensions, Lexer};
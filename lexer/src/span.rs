@@ -0,0 +1,69 @@
+/// a position within the source code, `offset` is the byte offset from the start
+/// of the source, while `line` and `column` are both 1-based and meant to be
+/// shown to humans, so `column` counts characters rather than bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// the region of source code a token was collected from, both `start` and `end`
+/// are inclusive, pointing to the first and last character of the run of
+/// repeated tokens respectively
+///
+/// given the source `+ +\n+`, the single `Increment(3)` token would span from
+/// line 1, column 1 to line 2, column 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Position {
+    pub fn new(offset: usize, line: usize, column: usize) -> Position {
+        Position {
+            offset,
+            line,
+            column,
+        }
+    }
+
+    /// moves the position past `byte`, columns count characters, so we only
+    /// move to the next column on bytes that start a new utf-8 character
+    pub(crate) fn advance(&mut self, byte: u8) {
+        self.offset += 1;
+        match byte {
+            b'\n' => {
+                self.line += 1;
+                self.column = 1;
+            }
+            byte if byte & 0xC0 != 0x80 => self.column += 1,
+            _ => {}
+        }
+    }
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Span {
+        Span { start, end }
+    }
+
+    /// a span covering both `self` and `other`, which is expected to come after
+    /// `self` in the source code
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start, other.end)
+    }
+}
+
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}, col {}", self.line, self.column)
+    }
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.start)
+    }
+}
@@ -23,11 +23,22 @@ pub enum Token {
     /// if the byte at the data pointer is zero, then instead of moving the
     /// instruction pointer to the next instruction, move it to the instruction
     /// right after the matching closing ´]´
+    ///
+    /// jumps are never grouped by the lexer, as every bracket has its own
+    /// matching pair, so the count here is always 1
     JumpIfZero(usize),
     /// if the byte at the data pointer is non zero, then instead of moving the
     /// instruction pointer to the next instruction, move it back to the
     /// instruction right after the matching opening ´[´
+    ///
+    /// just like `JumpIfZero`, this is never grouped by the lexer
     JumpUnlessZero(usize),
+    /// `#` is not part of brainfuck, but many tools use it to dump the cells
+    /// around the data pointer for debugging, it is only a token when enabled
+    /// through `Extensions`, otherwise it is a comment like any other byte
+    ///
+    /// every dump happens on its own, so this is never grouped by the lexer
+    Dump(usize),
 }
 
 /// this custom partial equality check exists as for this implementation, we
@@ -50,6 +61,7 @@ impl PartialEq for Token {
                 | (Token::Read(_), Token::Read(_))
                 | (Token::JumpIfZero(_), Token::JumpIfZero(_))
                 | (Token::JumpUnlessZero(_), Token::JumpUnlessZero(_))
+                | (Token::Dump(_), Token::Dump(_))
         )
     }
 }
@@ -68,8 +80,20 @@ impl Token {
             Token::Read(count) => count,
             Token::JumpIfZero(count) => count,
             Token::JumpUnlessZero(count) => count,
+            Token::Dump(count) => count,
         }
     }
+
+    /// wether this token is one of the jump instructions, `[` or `]`
+    pub fn is_jump(&self) -> bool {
+        matches!(self, Token::JumpIfZero(_) | Token::JumpUnlessZero(_))
+    }
+
+    /// wether repetitions of this token are collected into a single one, which
+    /// is true for every token but jumps and dumps
+    pub fn is_grouped(&self) -> bool {
+        !self.is_jump() && !matches!(self, Token::Dump(_))
+    }
 }
 
 /// when tokenizing the input, we convert the string slice into a byte array,
@@ -106,6 +130,7 @@ impl std::fmt::Display for Token {
             Token::Read(count) => write!(f, "{},", count),
             Token::JumpIfZero(count) => write!(f, "{}[", count),
             Token::JumpUnlessZero(count) => write!(f, "{}]", count),
+            Token::Dump(count) => write!(f, "{}#", count),
         }
     }
 }
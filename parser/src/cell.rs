@@ -0,0 +1,71 @@
+/// the size of every cell on the tape, brainfuck traditionally uses 8-bit cells,
+/// but many programs are written assuming wider cells
+///
+/// no matter the width, cells always wrap around on overflow and underflow, and
+/// io always happens one byte at a time, a byte read from the input is stored
+/// zero extended into the cell, and writing a cell outputs its lowest byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CellWidth {
+    #[default]
+    U8,
+    U16,
+    U32,
+}
+
+impl CellWidth {
+    pub fn bits(&self) -> u32 {
+        match self {
+            CellWidth::U8 => 8,
+            CellWidth::U16 => 16,
+            CellWidth::U32 => 32,
+        }
+    }
+
+    /// the size of a cell in bytes, which is how far apart cells are from each
+    /// other in memory on compiled programs
+    pub fn bytes(&self) -> usize {
+        self.bits() as usize / 8
+    }
+
+    /// the biggest value a cell can hold, every bit of a cell set
+    pub fn mask(&self) -> u32 {
+        u32::MAX >> (32 - self.bits())
+    }
+
+    /// wraps `value` into the range of a signed cell of this width, this is how
+    /// much a cell effectively changes when adding `value` to it
+    ///
+    /// ```rust
+    /// use parser::cell::CellWidth;
+    ///
+    /// assert_eq!(CellWidth::U8.wrap(257), 1);
+    /// assert_eq!(CellWidth::U8.wrap(255), -1);
+    /// assert_eq!(CellWidth::U16.wrap(255), 255);
+    /// ```
+    pub fn wrap(&self, value: i32) -> i32 {
+        match self {
+            CellWidth::U8 => value as i8 as i32,
+            CellWidth::U16 => value as i16 as i32,
+            CellWidth::U32 => value,
+        }
+    }
+}
+
+impl TryFrom<u32> for CellWidth {
+    type Error = &'static str;
+
+    fn try_from(bits: u32) -> Result<CellWidth, Self::Error> {
+        match bits {
+            8 => Ok(CellWidth::U8),
+            16 => Ok(CellWidth::U16),
+            32 => Ok(CellWidth::U32),
+            _ => Err("cell width must be 8, 16 or 32 bits"),
+        }
+    }
+}
+
+impl std::fmt::Display for CellWidth {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.bits())
+    }
+}
@@ -0,0 +1,36 @@
+/// what happens to the cell being read into when `,` is executed and there is
+/// no more input to be read, brainfuck doesn't specify it, so every convention
+/// found in the wild is supported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EofPolicy {
+    /// the cell keeps whatever value it had before reading
+    #[default]
+    Unchanged,
+    /// the cell is set to 0
+    Zero,
+    /// the cell is set to -1, which is every bit set, or 255 for 8-bit cells
+    MinusOne,
+}
+
+impl TryFrom<&str> for EofPolicy {
+    type Error = &'static str;
+
+    fn try_from(value: &str) -> Result<EofPolicy, Self::Error> {
+        match value {
+            "unchanged" => Ok(EofPolicy::Unchanged),
+            "zero" | "0" => Ok(EofPolicy::Zero),
+            "minus-one" | "-1" => Ok(EofPolicy::MinusOne),
+            _ => Err("eof policy must be one of unchanged, zero or minus-one"),
+        }
+    }
+}
+
+impl std::fmt::Display for EofPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EofPolicy::Unchanged => write!(f, "unchanged"),
+            EofPolicy::Zero => write!(f, "zero"),
+            EofPolicy::MinusOne => write!(f, "minus-one"),
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use lexer::span::Span;
+
+/// errors found while validating a program before running or compiling it,
+/// every variant carries the `Span` of the offending token so we can point at
+/// the exact place in the source code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// a `[` that is never closed by a matching `]`
+    UnmatchedOpen(Span),
+    /// a `]` that has no `[` before it to be matched with
+    UnmatchedClose(Span),
+}
+
+impl ParseError {
+    /// the `Span` of the bracket that caused this error
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnmatchedOpen(span) => *span,
+            ParseError::UnmatchedClose(span) => *span,
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseError::UnmatchedOpen(span) => write!(f, "unmatched `[` at {}", span),
+            ParseError::UnmatchedClose(span) => write!(f, "unmatched `]` at {}", span),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// errors that happen while a program is being executed (or compiled) by an
+/// `InstructionHandler`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeError {
+    /// a jump was reached at `instruction_ptr` but there is no matching bracket
+    /// for it, which can only happen when a handler is given a jump it did not
+    /// expect to handle
+    UnmatchedJump { instruction_ptr: usize },
+    /// the program used up all of its fuel, after running `steps` operations,
+    /// and was stopped right before running the operation at `instruction_ptr`
+    OutOfFuel {
+        steps: u64,
+        instruction_ptr: usize,
+        span: Span,
+    },
+    /// the program tried to reach `cell`, relative to where the data pointer
+    /// started, which is not on the tape according to the tape policy
+    OutOfTape {
+        cell: isize,
+        instruction_ptr: usize,
+        span: Span,
+    },
+    /// the program ran for longer than its timeout, after running `steps`
+    /// operations, and was stopped right before running the operation at
+    /// `instruction_ptr`
+    TimedOut {
+        steps: u64,
+        instruction_ptr: usize,
+        span: Span,
+    },
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RuntimeError::UnmatchedJump { instruction_ptr } => {
+                write!(f, "unmatched jump at instruction {}", instruction_ptr)
+            }
+            RuntimeError::OutOfFuel { steps, span, .. } => {
+                write!(f, "ran out of fuel after {} steps at {}", steps, span)
+            }
+            RuntimeError::OutOfTape { cell, span, .. } => {
+                write!(
+                    f,
+                    "tried to reach cell {} outside of the tape at {}",
+                    cell, span
+                )
+            }
+            RuntimeError::TimedOut { steps, span, .. } => {
+                write!(f, "timed out after {} steps at {}", steps, span)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// every error that can happen from reading a program to running it to
+/// completion
+#[derive(Debug)]
+pub enum Error {
+    /// reading the source code, reading input or writing output failed
+    Io(std::io::Error),
+    /// the source code is not a valid program
+    Parse(ParseError),
+    /// the program failed while running
+    Runtime(RuntimeError),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Parse(e) => write!(f, "{}", e),
+            Error::Runtime(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            Error::Runtime(e) => Some(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Error {
+        Error::Parse(e)
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(e: RuntimeError) -> Error {
+        Error::Runtime(e)
+    }
+}
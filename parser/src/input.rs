@@ -0,0 +1,65 @@
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read};
+
+/// a reader that is fed input a little at a time, for programs that run in
+/// slices through `Parser::run_for`, reading from it while it is empty pauses
+/// the program with `Status::NeedsInput` instead of reaching the end of input,
+/// which only happens once it is closed
+///
+/// ```rust
+/// use parser::input::Input;
+/// use std::io::Read;
+///
+/// let mut input = Input::new();
+/// let mut byte = [0; 1];
+/// assert!(input.read(&mut byte).is_err());
+///
+/// input.push(b"a");
+/// input.close();
+/// assert_eq!(input.read(&mut byte).unwrap(), 1);
+/// assert_eq!(input.read(&mut byte).unwrap(), 0);
+/// ```
+#[derive(Debug, Default)]
+pub struct Input {
+    buffer: VecDeque<u8>,
+    closed: bool,
+}
+
+impl Input {
+    pub fn new() -> Input {
+        Input::default()
+    }
+
+    /// adds `bytes` to the end of the input
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend(bytes);
+    }
+
+    /// marks that no more input is coming, once whatever is left is read, the
+    /// program sees the end of input
+    pub fn close(&mut self) {
+        self.closed = true;
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// how many bytes are waiting to be read
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.buffer.is_empty() && !self.closed {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        self.buffer.read(buf)
+    }
+}
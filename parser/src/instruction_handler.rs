@@ -1,72 +1,102 @@
-use lexer::token::Token;
+use crate::error::Error;
+use crate::program::Program;
 
 /// A instruction handler is anything that is capable of handling every
 /// brainfuck instruction, we made this a generic trait as we have a
 /// interpreter, which handler brainfuck code in runtime. And a compiler
 /// that turns brainfuck into assembly.
+///
+/// handlers act on the operations of the intermediate representation rather
+/// than on tokens, so every backend benefits from the same optimizations, see
+/// `parser::ir::Op` for what each of them means.
+///
+/// handlers report failures through `Error` instead of panicking, execution
+/// stops at the first error returned.
+///
+/// every handler receives the validated `Program` being executed, which gives
+/// access to the operations and to the jump table, so matching brackets can be
+/// found without scanning the operations.
 pub trait InstructionHandler<W, R>
 where
     W: std::io::Write,
     R: std::io::Read,
 {
-    fn move_ptr_left(
+    fn add(
         &mut self,
-        count: usize,
-        tokens: &[Token],
+        offset: isize,
+        value: i32,
+        program: &Program,
         writer: &mut W,
         instruction_ptr: &mut usize,
-    );
-    fn move_ptr_right(
+    ) -> Result<(), Error>;
+    fn move_ptr(
         &mut self,
-        count: usize,
-        tokens: &[Token],
+        amount: isize,
+        program: &Program,
         writer: &mut W,
         instruction_ptr: &mut usize,
-    );
-    fn increment_ptr(
+    ) -> Result<(), Error>;
+    fn write_ptr(
         &mut self,
+        offset: isize,
         count: usize,
-        tokens: &[Token],
+        program: &Program,
         writer: &mut W,
         instruction_ptr: &mut usize,
-    );
-    fn decrement_ptr(
+    ) -> Result<(), Error>;
+    #[allow(clippy::too_many_arguments)]
+    fn read_ptr(
         &mut self,
+        offset: isize,
         count: usize,
-        tokens: &[Token],
+        program: &Program,
         writer: &mut W,
+        reader: &mut R,
         instruction_ptr: &mut usize,
-    );
-    fn write_ptr(
+    ) -> Result<(), Error>;
+    fn set_zero(
         &mut self,
-        count: usize,
-        tokens: &[Token],
+        offset: isize,
+        program: &Program,
         writer: &mut W,
         instruction_ptr: &mut usize,
-    );
-    fn read_ptr(
+    ) -> Result<(), Error>;
+    fn mul_add(
         &mut self,
-        count: usize,
-        tokens: &[Token],
+        offset: isize,
+        factor: i32,
+        program: &Program,
         writer: &mut W,
-        reader: &mut R,
         instruction_ptr: &mut usize,
-    );
+    ) -> Result<(), Error>;
+    fn scan(
+        &mut self,
+        step: isize,
+        program: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error>;
     fn jump_if_zero(
         &mut self,
-        count: usize,
-        tokens: &[Token],
+        program: &Program,
         writer: &mut W,
         instruction_ptr: &mut usize,
-    );
+    ) -> Result<(), Error>;
     fn jump_unless_zero(
         &mut self,
-        count: usize,
-        tokens: &[Token],
+        program: &Program,
+        writer: &mut W,
+        instruction_ptr: &mut usize,
+    ) -> Result<(), Error>;
+    fn dump(
+        &mut self,
+        program: &Program,
         writer: &mut W,
         instruction_ptr: &mut usize,
-    );
+    ) -> Result<(), Error>;
 
     #[allow(unused_variables)]
-    fn finish(&mut self, writer: &mut W) {}
+    fn finish(&mut self, writer: &mut W) -> Result<(), Error> {
+        Ok(())
+    }
 }
@@ -0,0 +1,91 @@
+use lexer::token::Token;
+
+/// the intermediate representation shared by every backend, tokens are lowered
+/// into operations 1:1, and then the optimizer can rewrite sequences of them into
+/// more specialized operations, like clearing a cell or multiplying it into
+/// another one, that would otherwise take many instructions to execute
+///
+/// every operation that touches a cell is addressed by an `offset` relative to
+/// the data pointer, this lets the optimizer get rid of most pointer movements
+/// in straight-line code, e.g: `>+>++<<` becomes `Add { offset: 1, value: 1 }`
+/// and `Add { offset: 2, value: 2 }` without moving the pointer at all
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// adds `value` to the cell `offset` cells away from the data pointer,
+    /// wrapping on overflow in both directions, `value` is kept as wide as the
+    /// widest cell supported, so backends have to wrap it to their own width
+    Add { offset: isize, value: i32 },
+    /// moves the data pointer by the given amount of cells, negative amounts
+    /// move to the left
+    Move(isize),
+    /// outputs the cell `offset` cells away from the data pointer `count` times
+    Write { offset: isize, count: usize },
+    /// reads `count` bytes from the input into the cell `offset` cells away from
+    /// the data pointer, only the last one read remains in the cell
+    Read { offset: isize, count: usize },
+    /// sets the cell `offset` cells away from the data pointer to zero, this is
+    /// what loops like `[-]` are turned into
+    SetZero { offset: isize },
+    /// adds the current cell multiplied by `factor` to the cell `offset` cells
+    /// away from the data pointer, loops like `[->++<]` become a `MulAdd` for
+    /// every cell they touch followed by a `SetZero` of the current cell
+    MulAdd { offset: isize, factor: i32 },
+    /// moves the data pointer by `step` cells until it points to a zero cell,
+    /// which is what loops like `[>]` and `[<<]` are turned into
+    Scan(isize),
+    /// same as `Token::JumpIfZero`
+    JumpIfZero,
+    /// same as `Token::JumpUnlessZero`
+    JumpUnlessZero,
+    /// same as `Token::Dump`, this has no effect on the program itself, so
+    /// backends are free to ignore it
+    Dump,
+}
+
+impl Op {
+    /// wether this operation is one of the jump instructions, `[` or `]`
+    pub fn is_jump(&self) -> bool {
+        matches!(self, Op::JumpIfZero | Op::JumpUnlessZero)
+    }
+}
+
+/// lowering of a single token into its operation, no optimizations happen
+/// here, so the resulting operations map 1:1 to the given tokens
+impl From<Token> for Op {
+    fn from(token: Token) -> Op {
+        match token {
+            Token::MoveLeft(count) => Op::Move(-(count as isize)),
+            Token::MoveRight(count) => Op::Move(count as isize),
+            Token::Increment(count) => Op::Add {
+                offset: 0,
+                value: count as u32 as i32,
+            },
+            Token::Decrement(count) => Op::Add {
+                offset: 0,
+                value: (count as u32).wrapping_neg() as i32,
+            },
+            Token::Write(count) => Op::Write { offset: 0, count },
+            Token::Read(count) => Op::Read { offset: 0, count },
+            Token::JumpIfZero(_) => Op::JumpIfZero,
+            Token::JumpUnlessZero(_) => Op::JumpUnlessZero,
+            Token::Dump(_) => Op::Dump,
+        }
+    }
+}
+
+impl std::fmt::Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Op::Add { offset, value } => write!(f, "add {} @{}", value, offset),
+            Op::Move(amount) => write!(f, "move {}", amount),
+            Op::Write { offset, count } => write!(f, "write {} @{}", count, offset),
+            Op::Read { offset, count } => write!(f, "read {} @{}", count, offset),
+            Op::SetZero { offset } => write!(f, "zero @{}", offset),
+            Op::MulAdd { offset, factor } => write!(f, "muladd {} @{}", factor, offset),
+            Op::Scan(step) => write!(f, "scan {}", step),
+            Op::JumpIfZero => write!(f, "["),
+            Op::JumpUnlessZero => write!(f, "]"),
+            Op::Dump => write!(f, "#"),
+        }
+    }
+}
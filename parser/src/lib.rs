@@ -1,4 +1,13 @@
-mod parser;
+pub mod cell;
+pub mod eof;
+pub mod error;
+pub mod input;
 pub mod instruction_handler;
+pub mod ir;
+pub mod limits;
+mod optimizer;
+mod parser;
+pub mod program;
+pub mod tape;
 
-pub use parser::Parser;
+pub use parser::{Parser, Status};
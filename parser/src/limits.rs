@@ -0,0 +1,32 @@
+use std::time::Duration;
+
+/// how often, in steps, the deadline is checked, asking for the time on every
+/// single step would slow down interpretation considerably
+pub(crate) const DEADLINE_CHECK_INTERVAL: u64 = 1024;
+
+/// bounds on how long a program is allowed to run, useful when running code
+/// that can't be trusted to ever finish, like `+[]`
+///
+/// nothing is limited by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Limits {
+    /// the maximum amount of steps to execute, a step being a single operation
+    /// dispatched to the `InstructionHandler`
+    pub fuel: Option<u64>,
+    /// the maximum amount of time to run for, counting only the time spent
+    /// inside of `Parser::run_for`, so a program paused in the debugger isn't
+    /// timed out, and checked every `DEADLINE_CHECK_INTERVAL` steps
+    pub timeout: Option<Duration>,
+}
+
+impl Limits {
+    pub fn with_fuel(mut self, fuel: u64) -> Limits {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Limits {
+        self.timeout = Some(timeout);
+        self
+    }
+}
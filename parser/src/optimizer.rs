@@ -0,0 +1,371 @@
+use crate::ir::Op;
+use lexer::span::Span;
+
+use std::collections::BTreeMap;
+
+/// runs every optimization pass over the given operations, the operations are
+/// expected to have balanced jumps, and the result will too
+///
+/// the order of the passes matter, loops are only recognized after folding
+/// repeated operations together, and offsets are only computed after loops
+/// were simplified, as loops are barriers for pointer movements
+pub fn optimize(ops: Vec<(Op, Span)>) -> Vec<(Op, Span)> {
+    let ops = fold(ops);
+    let ops = simplify_loops(ops);
+    let ops = defer_moves(ops);
+    fold(ops)
+}
+
+/// combines adjacent operations that act on the same thing, like two `Add`
+/// on the same cell, or two `Move`, dropping the ones that cancel each other
+/// out completely like `+-` or `<>`
+fn fold(ops: Vec<(Op, Span)>) -> Vec<(Op, Span)> {
+    let mut folded: Vec<(Op, Span)> = vec![];
+
+    for (op, span) in ops {
+        let Some((last, last_span)) = folded.last_mut() else {
+            folded.push((op, span));
+            continue;
+        };
+
+        let combined = match (*last, op) {
+            (
+                Op::Add { offset, value },
+                Op::Add {
+                    offset: other,
+                    value: rhs,
+                },
+            ) if offset == other => Some(Op::Add {
+                offset,
+                value: value.wrapping_add(rhs),
+            }),
+            (Op::Move(amount), Op::Move(rhs)) => Some(Op::Move(amount + rhs)),
+            // adding to a cell that is cleared right after is useless
+            (Op::Add { offset, .. }, Op::SetZero { offset: other }) if offset == other => {
+                Some(Op::SetZero { offset })
+            }
+            _ => None,
+        };
+
+        match combined {
+            Some(Op::Add { value: 0, .. }) | Some(Op::Move(0)) => _ = folded.pop(),
+            Some(combined) => {
+                *last = combined;
+                *last_span = last_span.to(span);
+            }
+            None => folded.push((op, span)),
+        }
+    }
+
+    folded
+}
+
+/// replaces innermost loops that follow well known patterns with specialized
+/// operations:
+///
+/// - `[-]` and `[+]` become a `SetZero`
+/// - `[>]` and `[<<]` become a `Scan`
+/// - `[->+>++<<]` become one `MulAdd` for each cell touched, followed by a
+///   `SetZero` of the current cell
+fn simplify_loops(ops: Vec<(Op, Span)>) -> Vec<(Op, Span)> {
+    let mut simplified: Vec<(Op, Span)> = vec![];
+    let mut open_jumps = vec![];
+
+    for (op, span) in ops {
+        match op {
+            Op::JumpIfZero => {
+                open_jumps.push(simplified.len());
+                simplified.push((op, span));
+            }
+            Op::JumpUnlessZero => {
+                let open = open_jumps
+                    .pop()
+                    .expect("optimizing a program with unbalanced jumps");
+                let body = simplified[open + 1..]
+                    .iter()
+                    .map(|(op, _)| *op)
+                    .collect::<Vec<_>>();
+
+                match simplify_loop(&body) {
+                    Some(replacement) => {
+                        let span = simplified[open].1.to(span);
+                        simplified.truncate(open);
+                        simplified.extend(replacement.into_iter().map(|op| (op, span)));
+                    }
+                    None => simplified.push((op, span)),
+                }
+            }
+            _ => simplified.push((op, span)),
+        }
+    }
+
+    simplified
+}
+
+/// tries to find a simpler set of operations that does the same as a loop with
+/// the given body
+fn simplify_loop(body: &[Op]) -> Option<Vec<Op>> {
+    match body {
+        // any odd step will eventually reach zero, even when wrapping
+        [Op::Add { offset: 0, value }] if value % 2 != 0 => {
+            return Some(vec![Op::SetZero { offset: 0 }])
+        }
+        [Op::Move(step)] => return Some(vec![Op::Scan(*step)]),
+        _ => {}
+    }
+
+    // multiplication loops only move the pointer around and add to cells, as
+    // long as they come back to where they started and the current cell is
+    // used as a counter that goes down (or up) by one
+    let mut position = 0;
+    let mut deltas = BTreeMap::new();
+    for op in body {
+        match op {
+            Op::Add { offset, value } => {
+                let delta = deltas.entry(position + offset).or_insert(0i32);
+                *delta = delta.wrapping_add(*value);
+            }
+            Op::Move(amount) => position += amount,
+            _ => return None,
+        }
+    }
+
+    if position != 0 {
+        return None;
+    }
+
+    // when the counter goes up, the loop runs `256 - n` times (for 8-bit cells),
+    // which in a wrapping world is the same as `-n` times, so we negate the factor
+    let sign = match deltas.remove(&0) {
+        Some(-1) => 1,
+        Some(1) => -1,
+        _ => return None,
+    };
+
+    let mut replacement = deltas
+        .into_iter()
+        .filter(|(_, factor)| *factor != 0)
+        .map(|(offset, factor)| Op::MulAdd {
+            offset,
+            factor: factor.wrapping_mul(sign),
+        })
+        .collect::<Vec<_>>();
+    replacement.push(Op::SetZero { offset: 0 });
+    Some(replacement)
+}
+
+/// instead of moving the data pointer around on straight-line code, we keep
+/// track of where it would be and address cells by an offset from it, only
+/// moving the pointer before things that need it to be in the right place,
+/// like jumps, scans and dumps
+///
+/// operations that absorb pending moves span them too, so when the cell they
+/// reach is outside of the tape, the error points at the moves that got there
+fn defer_moves(ops: Vec<(Op, Span)>) -> Vec<(Op, Span)> {
+    let mut deferred = vec![];
+    let mut pending: Option<(isize, Span)> = None;
+
+    for (op, span) in ops {
+        let position = pending.map(|(amount, _)| amount).unwrap_or(0);
+        let span = match (op, pending) {
+            (
+                Op::Add { .. } | Op::Write { .. } | Op::Read { .. } | Op::SetZero { .. },
+                Some((amount, pending_span)),
+            ) if amount != 0 => pending_span.to(span),
+            _ => span,
+        };
+        match op {
+            Op::Move(amount) => {
+                pending = match pending {
+                    Some((total, pending_span)) => Some((total + amount, pending_span.to(span))),
+                    None => Some((amount, span)),
+                };
+            }
+            Op::Add { offset, value } => deferred.push((
+                Op::Add {
+                    offset: offset + position,
+                    value,
+                },
+                span,
+            )),
+            Op::Write { offset, count } => deferred.push((
+                Op::Write {
+                    offset: offset + position,
+                    count,
+                },
+                span,
+            )),
+            Op::Read { offset, count } => deferred.push((
+                Op::Read {
+                    offset: offset + position,
+                    count,
+                },
+                span,
+            )),
+            Op::SetZero { offset } => deferred.push((
+                Op::SetZero {
+                    offset: offset + position,
+                },
+                span,
+            )),
+            Op::MulAdd { .. } | Op::Scan(_) | Op::JumpIfZero | Op::JumpUnlessZero | Op::Dump => {
+                if let Some((amount, pending_span)) = pending.take() {
+                    if amount != 0 {
+                        deferred.push((Op::Move(amount), pending_span));
+                    }
+                }
+                deferred.push((op, span));
+            }
+        }
+    }
+
+    if let Some((amount, pending_span)) = pending {
+        if amount != 0 {
+            deferred.push((Op::Move(amount), pending_span));
+        }
+    }
+
+    deferred
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::Program;
+
+    fn check(source: &str) -> Vec<Op> {
+        Program::parse(source).unwrap().optimize().ops().to_vec()
+    }
+
+    #[test]
+    fn folding_cancels_out() {
+        assert_eq!(check("+-<>"), vec![]);
+        assert_eq!(
+            check("++-"),
+            vec![Op::Add {
+                offset: 0,
+                value: 1
+            }]
+        );
+        assert_eq!(check(">><"), vec![Op::Move(1)]);
+    }
+
+    #[test]
+    fn clear_loops() {
+        assert_eq!(check("[-]"), vec![Op::SetZero { offset: 0 }]);
+        assert_eq!(check("[+++]"), vec![Op::SetZero { offset: 0 }]);
+        assert_eq!(check("+++[-]"), vec![Op::SetZero { offset: 0 }]);
+        // even steps might never reach zero, so they are left alone
+        assert_eq!(
+            check("[--]"),
+            vec![
+                Op::JumpIfZero,
+                Op::Add {
+                    offset: 0,
+                    value: -2
+                },
+                Op::JumpUnlessZero
+            ]
+        );
+    }
+
+    #[test]
+    fn scan_loops() {
+        assert_eq!(check("[>]"), vec![Op::Scan(1)]);
+        assert_eq!(check("[<<]"), vec![Op::Scan(-2)]);
+    }
+
+    #[test]
+    fn multiplication_loops() {
+        assert_eq!(
+            check("[->+>+++<<]"),
+            vec![
+                Op::MulAdd {
+                    offset: 1,
+                    factor: 1
+                },
+                Op::MulAdd {
+                    offset: 2,
+                    factor: 3
+                },
+                Op::SetZero { offset: 0 },
+            ]
+        );
+        assert_eq!(
+            check("[<-->+]"),
+            vec![
+                Op::MulAdd {
+                    offset: -1,
+                    factor: 2
+                },
+                Op::SetZero { offset: 0 },
+            ]
+        );
+        // loops that don't come back to the same cell are not multiplications
+        assert_eq!(check("[->+]").len(), 5);
+        // neither are loops that do io
+        assert_eq!(
+            check("[->+.<]"),
+            vec![
+                Op::JumpIfZero,
+                Op::Add {
+                    offset: 0,
+                    value: -1
+                },
+                Op::Add {
+                    offset: 1,
+                    value: 1
+                },
+                Op::Write {
+                    offset: 1,
+                    count: 1
+                },
+                Op::JumpUnlessZero,
+            ]
+        );
+    }
+
+    #[test]
+    fn deferred_moves() {
+        assert_eq!(
+            check(">+>++<.[-]>"),
+            vec![
+                Op::Add {
+                    offset: 1,
+                    value: 1
+                },
+                Op::Add {
+                    offset: 2,
+                    value: 2
+                },
+                Op::Write {
+                    offset: 1,
+                    count: 1
+                },
+                Op::SetZero { offset: 1 },
+                Op::Move(2),
+            ]
+        );
+        // dumps show the cells around the pointer, so it has to be in place
+        let program = Program::parse_with(">>#<", lexer::Extensions { dump: true }).unwrap();
+        assert_eq!(
+            program.optimize().ops(),
+            &[Op::Move(2), Op::Dump, Op::Move(-1)]
+        );
+        // errors on cells reached through deferred moves point at the moves
+        let program = Program::parse("<[-]").unwrap().optimize();
+        assert_eq!(program.ops(), &[Op::SetZero { offset: -1 }, Op::Move(-1)]);
+        assert_eq!(program.spans()[0].start.column, 1);
+        assert_eq!(
+            check(">+[<]"),
+            vec![
+                Op::Add {
+                    offset: 1,
+                    value: 1
+                },
+                Op::Move(1),
+                Op::Scan(-1),
+            ]
+        );
+    }
+}
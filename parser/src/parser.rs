@@ -1,7 +1,28 @@
+use crate::error::{Error, RuntimeError};
 use crate::instruction_handler::InstructionHandler;
-use lexer::token::Token;
+use crate::ir::Op;
+use crate::limits::{Limits, DEADLINE_CHECK_INTERVAL};
+use crate::program::Program;
 
-use std::io::{Read, Write};
+use std::collections::BTreeSet;
+use std::io::{ErrorKind, Read, Write};
+use std::time::{Duration, Instant};
+
+/// where a loaded program is at in between slices of execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// there are still instructions to run
+    Running,
+    /// the instruction pointer went past the last instruction, so there is
+    /// nothing else to run
+    Halted,
+    /// the program tried to read but the reader had nothing to give yet, the
+    /// read is tried again on the next step, so execution can resume as soon as
+    /// there is more input
+    NeedsInput,
+    /// the next instruction to run has a breakpoint on it
+    Breakpoint(usize),
+}
 
 #[derive(Debug)]
 pub struct Parser<W, R, I>
@@ -10,18 +31,33 @@ where
     R: Read,
     I: InstructionHandler<W, R> + std::fmt::Debug,
 {
+    /// the program being run, empty until one is loaded
+    program: Program,
     /// the pointer to the current instruction to be executed within the
-    /// vector of tokens
+    /// operations of the program
     instruction_ptr: usize,
     /// generic writer to make testing easier, anything that impls Write can be used
     /// here
     writer: W,
     /// generic reader to make testing easier, anything that impls Read can be used
     /// here
+    ///
+    /// readers that are not ready to be read from yet must fail with
+    /// `ErrorKind::WouldBlock`, which pauses the program until there is input
     reader: R,
     /// instruction handler that will handle each action, this is generic as we have
     /// a interpreter and a compiler.
     instruction_handler: I,
+    /// how long the program is allowed to run for, unlimited by default
+    limits: Limits,
+    /// how many operations were dispatched to the instruction handler since the
+    /// program was loaded
+    steps: u64,
+    /// how long `run_for` spent running the program since it was loaded,
+    /// which is what the timeout is measured against
+    elapsed: Duration,
+    /// indexes of the instructions that pause `run_for` before running them
+    breakpoints: BTreeSet<usize>,
 }
 
 impl<W, R, I> Parser<W, R, I>
@@ -32,17 +68,39 @@ where
 {
     pub fn new(writer: W, reader: R, instruction_handler: I) -> Parser<W, R, I> {
         Parser {
+            program: Program::default(),
             writer,
             reader,
             instruction_handler,
             instruction_ptr: 0,
+            limits: Limits::default(),
+            steps: 0,
+            elapsed: Duration::ZERO,
+            breakpoints: BTreeSet::new(),
         }
     }
 
+    pub fn with_limits(mut self, limits: Limits) -> Parser<W, R, I> {
+        self.limits = limits;
+        self
+    }
+
     pub fn writer(&self) -> &W {
         &self.writer
     }
 
+    pub fn writer_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn reader(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn reader_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
     pub fn instruction_ptr(&self) -> usize {
         self.instruction_ptr
     }
@@ -51,60 +109,202 @@ where
         &self.instruction_handler
     }
 
-    pub fn interpret(&mut self, tokens: Vec<Token>) {
-        while self.instruction_ptr < tokens.len() {
-            match &tokens[self.instruction_ptr] {
-                Token::MoveLeft(count) => self.instruction_handler.move_ptr_left(
-                    *count,
-                    &tokens,
-                    &mut self.writer,
-                    &mut self.instruction_ptr,
-                ),
-                Token::MoveRight(count) => self.instruction_handler.move_ptr_right(
-                    *count,
-                    &tokens,
-                    &mut self.writer,
-                    &mut self.instruction_ptr,
-                ),
-                Token::Increment(count) => self.instruction_handler.increment_ptr(
-                    *count,
-                    &tokens,
-                    &mut self.writer,
-                    &mut self.instruction_ptr,
-                ),
-                Token::Decrement(count) => self.instruction_handler.decrement_ptr(
-                    *count,
-                    &tokens,
-                    &mut self.writer,
-                    &mut self.instruction_ptr,
-                ),
-                Token::Write(count) => self.instruction_handler.write_ptr(
-                    *count,
-                    &tokens,
-                    &mut self.writer,
-                    &mut self.instruction_ptr,
-                ),
-                Token::JumpIfZero(count) => self.instruction_handler.jump_if_zero(
-                    *count,
-                    &tokens,
-                    &mut self.writer,
-                    &mut self.instruction_ptr,
-                ),
-                Token::JumpUnlessZero(count) => self.instruction_handler.jump_unless_zero(
-                    *count,
-                    &tokens,
-                    &mut self.writer,
-                    &mut self.instruction_ptr,
-                ),
-                Token::Read(count) => self.instruction_handler.read_ptr(
-                    *count,
-                    &tokens,
-                    &mut self.writer,
-                    &mut self.reader,
-                    &mut self.instruction_ptr,
-                ),
+    pub fn instruction_handler_mut(&mut self) -> &mut I {
+        &mut self.instruction_handler
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// replaces the program being run, starting it over from its first
+    /// instruction, the instruction handler is left as is, so a program can
+    /// pick up the state left behind by the previous one
+    ///
+    /// breakpoints belong to the previous program, so they are removed
+    pub fn load(&mut self, program: Program) {
+        self.program = program;
+        self.instruction_ptr = 0;
+        self.steps = 0;
+        self.elapsed = Duration::ZERO;
+        self.breakpoints.clear();
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// adds a breakpoint to the instruction at `index` of the loaded program,
+    /// returning false when there is no such instruction
+    pub fn add_breakpoint(&mut self, index: usize) -> bool {
+        if index >= self.program.len() {
+            return false;
+        }
+        self.breakpoints.insert(index);
+        true
+    }
+
+    /// removes the breakpoint at `index`, returning false when there was none
+    pub fn remove_breakpoint(&mut self, index: usize) -> bool {
+        self.breakpoints.remove(&index)
+    }
+
+    /// whether the instruction pointer went past the last operation of the
+    /// loaded program
+    pub fn is_finished(&self) -> bool {
+        self.instruction_ptr >= self.program.len()
+    }
+
+    /// where the loaded program is at, this is never `NeedsInput`, as that is
+    /// only known when trying to read
+    pub fn status(&self) -> Status {
+        if self.is_finished() {
+            Status::Halted
+        } else if self.breakpoints.contains(&self.instruction_ptr) {
+            Status::Breakpoint(self.instruction_ptr)
+        } else {
+            Status::Running
+        }
+    }
+
+    /// runs a single operation of the loaded program, the one at the
+    /// instruction pointer, leaving the instruction pointer at the next
+    /// operation to run
+    ///
+    /// stepping does not care about `Limits`, use `run_for` for that, and
+    /// stepping a finished program does nothing
+    pub fn step(&mut self) -> Result<Status, Error> {
+        if self.is_finished() {
+            return Ok(Status::Halted);
+        }
+
+        let program = &self.program;
+        let handler = &mut self.instruction_handler;
+        let writer = &mut self.writer;
+        let instruction_ptr = &mut self.instruction_ptr;
+
+        let result = match program.ops()[*instruction_ptr] {
+            Op::Add { offset, value } => {
+                handler.add(offset, value, program, writer, instruction_ptr)
+            }
+            Op::Move(amount) => handler.move_ptr(amount, program, writer, instruction_ptr),
+            Op::Write { offset, count } => {
+                handler.write_ptr(offset, count, program, writer, instruction_ptr)
+            }
+            Op::Read { offset, count } => handler.read_ptr(
+                offset,
+                count,
+                program,
+                writer,
+                &mut self.reader,
+                instruction_ptr,
+            ),
+            Op::SetZero { offset } => handler.set_zero(offset, program, writer, instruction_ptr),
+            Op::MulAdd { offset, factor } => {
+                handler.mul_add(offset, factor, program, writer, instruction_ptr)
+            }
+            Op::Scan(step) => handler.scan(step, program, writer, instruction_ptr),
+            Op::JumpIfZero => handler.jump_if_zero(program, writer, instruction_ptr),
+            Op::JumpUnlessZero => handler.jump_unless_zero(program, writer, instruction_ptr),
+            Op::Dump => handler.dump(program, writer, instruction_ptr),
+        };
+
+        match result {
+            Err(Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => Ok(Status::NeedsInput),
+            Err(e) => Err(e),
+            Ok(()) => {
+                self.steps += 1;
+                Ok(self.status())
+            }
+        }
+    }
+
+    /// runs at most `steps` operations of the loaded program, stopping early
+    /// when the program halts, needs input, or reaches a breakpoint, and
+    /// failing when the program goes over one of its `Limits`
+    ///
+    /// the instruction about to run never stops the program, even if it has a
+    /// breakpoint, otherwise a program paused on a breakpoint could never move
+    /// past it
+    pub fn run_for(&mut self, steps: u64) -> Result<Status, Error> {
+        let started = Instant::now();
+        let result = self.run_limited(steps, started);
+        self.elapsed += started.elapsed();
+        result
+    }
+
+    fn run_limited(&mut self, steps: u64, started: Instant) -> Result<Status, Error> {
+        let mut status = self.status();
+        for _ in 0..steps {
+            if self.is_finished() {
+                break;
+            }
+            self.check_limits(started)?;
+
+            status = self.step()?;
+            if status != Status::Running {
+                break;
+            }
+        }
+        Ok(status)
+    }
+
+    /// fails when running the next operation would go over the fuel, or when
+    /// the program already ran for longer than the timeout, counting the time
+    /// spent in earlier calls to `run_for`
+    fn check_limits(&self, started: Instant) -> Result<(), Error> {
+        if self.limits.fuel.is_some_and(|fuel| self.steps >= fuel) {
+            return Err(RuntimeError::OutOfFuel {
+                steps: self.steps,
+                instruction_ptr: self.instruction_ptr,
+                span: self.program.spans()[self.instruction_ptr],
+            }
+            .into());
+        }
+
+        if self.steps.is_multiple_of(DEADLINE_CHECK_INTERVAL)
+            && self
+                .limits
+                .timeout
+                .is_some_and(|timeout| self.elapsed + started.elapsed() >= timeout)
+        {
+            return Err(RuntimeError::TimedOut {
+                steps: self.steps,
+                instruction_ptr: self.instruction_ptr,
+                span: self.program.spans()[self.instruction_ptr],
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// loads the program and runs it until the instruction pointer goes past
+    /// its last operation, until the instruction handler fails, or until the
+    /// program goes over one of its `Limits`
+    ///
+    /// breakpoints are ignored, and readers are expected to block until there
+    /// is input, as there is no way to resume the program afterwards
+    pub fn interpret(&mut self, program: Program) -> Result<(), Error> {
+        self.load(program);
+
+        loop {
+            match self.run_for(u64::MAX)? {
+                Status::Halted => break,
+                Status::NeedsInput => {
+                    return Err(std::io::Error::from(ErrorKind::WouldBlock).into())
+                }
+                Status::Running | Status::Breakpoint(_) => {}
             }
         }
-        self.instruction_handler.finish(&mut self.writer);
+        self.instruction_handler.finish(&mut self.writer)
     }
 }
@@ -0,0 +1,227 @@
+use crate::error::{Error, ParseError};
+use crate::ir::Op;
+use crate::optimizer;
+use lexer::span::Span;
+use lexer::token::Token;
+use lexer::{Extensions, Lexer};
+
+use std::path::Path;
+
+/// a brainfuck program that is known to be valid, meaning every `[` has a
+/// matching `]` and vice versa, so instruction handlers can rely on every jump
+/// having somewhere to land
+///
+/// the only way to build a `Program` is through `Program::new` or
+/// `Program::parse`, which validate the tokens before lowering them into
+/// operations, at this point every operation maps to a single token, and
+/// `Program::optimize` can be used to get an equivalent but faster program
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    ops: Vec<Op>,
+    /// the source code `Span` of each operation, `spans[i]` is where `ops[i]`
+    /// came from, optimized operations span every token they replaced
+    spans: Vec<Span>,
+    /// the jump table of the program, for every jump operation at `ops[i]` the
+    /// index of its matching bracket is stored at `jumps[i]`, so jumping is a
+    /// single lookup instead of a scan over the operations
+    jumps: Vec<Option<usize>>,
+}
+
+impl Program {
+    /// validates the bracket balance of the given spanned tokens, failing with
+    /// the first unmatched bracket found
+    ///
+    /// when both kinds of errors exist, a stray `]` is reported first, as it
+    /// is found while walking the tokens, while unclosed `[` are only known
+    /// once we reach the end of the program
+    pub fn new(tokens: Vec<(Token, Span)>) -> Result<Program, ParseError> {
+        let mut open_jumps = vec![];
+
+        for (token, span) in tokens.iter() {
+            match token {
+                Token::JumpIfZero(_) => open_jumps.push(*span),
+                Token::JumpUnlessZero(_) if open_jumps.pop().is_none() => {
+                    return Err(ParseError::UnmatchedClose(*span));
+                }
+                _ => {}
+            }
+        }
+
+        // the innermost unclosed bracket is the last one we pushed, but the
+        // outermost is usually the one that was forgotten, so we report that
+        if let Some(span) = open_jumps.first() {
+            return Err(ParseError::UnmatchedOpen(*span));
+        }
+
+        let ops = tokens
+            .into_iter()
+            .map(|(token, span)| (Op::from(token), span))
+            .collect();
+        Ok(Program::from_ops(ops))
+    }
+
+    /// builds the jump table for operations that are known to be balanced
+    fn from_ops(ops: Vec<(Op, Span)>) -> Program {
+        let (ops, spans): (Vec<Op>, Vec<Span>) = ops.into_iter().unzip();
+        let mut open_jumps = vec![];
+        let mut jumps = vec![None; ops.len()];
+
+        for (index, op) in ops.iter().enumerate() {
+            match op {
+                Op::JumpIfZero => open_jumps.push(index),
+                Op::JumpUnlessZero => {
+                    let open = open_jumps.pop().expect("unbalanced jumps on a program");
+                    jumps[open] = Some(index);
+                    jumps[index] = Some(open);
+                }
+                _ => {}
+            }
+        }
+
+        Program { ops, spans, jumps }
+    }
+
+    /// tokenizes and validates the given source code
+    ///
+    /// ```rust
+    /// use parser::program::Program;
+    /// use parser::error::ParseError;
+    ///
+    /// assert!(Program::parse("+[->+<]").is_ok());
+    ///
+    /// let error = Program::parse("+\n [->+<").unwrap_err();
+    /// assert!(matches!(error, ParseError::UnmatchedOpen(_)));
+    /// assert_eq!(error.to_string(), "unmatched `[` at line 2, col 2");
+    /// ```
+    pub fn parse(source: &str) -> Result<Program, ParseError> {
+        Program::parse_with(source, Extensions::default())
+    }
+
+    /// the same as `parse`, but with the given lexer `Extensions` enabled
+    pub fn parse_with(source: &str, extensions: Extensions) -> Result<Program, ParseError> {
+        Program::new(Lexer::tokenize_with(source, extensions))
+    }
+
+    /// reads the source code at `path` and parses it
+    pub fn from_file<P>(path: P) -> Result<Program, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Program::from_file_with(path, Extensions::default())
+    }
+
+    /// the same as `from_file`, but with the given lexer `Extensions` enabled
+    pub fn from_file_with<P>(path: P, extensions: Extensions) -> Result<Program, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let code = std::fs::read_to_string(path.as_ref())?;
+        Ok(Program::parse_with(&code, extensions)?)
+    }
+
+    /// runs the optimizer over this program, the resulting program behaves the
+    /// same, but usually has less operations, which are also more specialized
+    ///
+    /// ```rust
+    /// use parser::ir::Op;
+    /// use parser::program::Program;
+    ///
+    /// let program = Program::parse("+++[->++<]").unwrap().optimize();
+    /// assert_eq!(
+    ///     program.ops(),
+    ///     &[
+    ///         Op::Add { offset: 0, value: 3 },
+    ///         Op::MulAdd { offset: 1, factor: 2 },
+    ///         Op::SetZero { offset: 0 },
+    ///     ]
+    /// );
+    /// ```
+    pub fn optimize(self) -> Program {
+        let ops = self.ops.into_iter().zip(self.spans).collect();
+        Program::from_ops(optimizer::optimize(ops))
+    }
+
+    pub fn ops(&self) -> &[Op] {
+        &self.ops
+    }
+
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// the index of the bracket matching the jump at `index`, or `None` when
+    /// the operation at `index` is not a jump
+    ///
+    /// ```rust
+    /// use parser::program::Program;
+    ///
+    /// let program = Program::parse("+[>[-]<]").unwrap();
+    /// assert_eq!(program.jump_target(1), Some(7));
+    /// assert_eq!(program.jump_target(5), Some(3));
+    /// assert_eq!(program.jump_target(0), None);
+    /// ```
+    pub fn jump_target(&self, index: usize) -> Option<usize> {
+        self.jumps.get(index).copied().flatten()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balanced_programs() {
+        assert!(Program::parse("").is_ok());
+        assert!(Program::parse("[]").is_ok());
+        assert!(Program::parse("+[[-]>[<+>-]]").is_ok());
+        assert!(Program::parse(include_str!("../../samples/rot_13.bf")).is_ok());
+    }
+
+    #[test]
+    fn jump_table_matches_nested_brackets() {
+        let program = Program::parse("[[]][[-]]").unwrap();
+        let targets = (0..program.len())
+            .map(|index| program.jump_target(index))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            targets,
+            vec![
+                Some(3),
+                Some(2),
+                Some(1),
+                Some(0),
+                Some(8),
+                Some(7),
+                None,
+                Some(5),
+                Some(4)
+            ]
+        );
+    }
+
+    #[test]
+    fn unmatched_close() {
+        let error = Program::parse("+[-]]").unwrap_err();
+        assert_eq!(error.to_string(), "unmatched `]` at line 1, col 5");
+
+        let error = Program::parse("][").unwrap_err();
+        assert_eq!(error.to_string(), "unmatched `]` at line 1, col 1");
+    }
+
+    #[test]
+    fn unmatched_open() {
+        let error = Program::parse("+\n[[-]").unwrap_err();
+        assert_eq!(error.to_string(), "unmatched `[` at line 2, col 1");
+
+        let error = Program::parse("[]\n  [[]").unwrap_err();
+        assert_eq!(error.to_string(), "unmatched `[` at line 2, col 3");
+    }
+}
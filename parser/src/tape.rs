@@ -0,0 +1,106 @@
+use std::num::NonZeroUsize;
+
+/// the amount of cells on a tape that has a fixed size but no configured length,
+/// which is the size of the tape on the original brainfuck implementation
+pub const DEFAULT_TAPE_LEN: usize = 30000;
+
+/// what happens when the data pointer moves past the end of the tape
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// the tape grows to make room for the new cells, and only going over
+    /// `max_len` cells in use is an error
+    #[default]
+    Grow,
+    /// the tape has a fixed size, and moving past one of its ends takes the
+    /// data pointer to the other end
+    Wrap,
+    /// the tape has a fixed size, and moving past one of its ends is an error
+    Error,
+}
+
+impl TryFrom<&str> for Overflow {
+    type Error = &'static str;
+
+    fn try_from(value: &str) -> Result<Overflow, <Overflow as TryFrom<&str>>::Error> {
+        match value {
+            "grow" => Ok(Overflow::Grow),
+            "wrap" => Ok(Overflow::Wrap),
+            "error" => Ok(Overflow::Error),
+            _ => Err("tape overflow must be one of grow, wrap or error"),
+        }
+    }
+}
+
+impl std::fmt::Display for Overflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Overflow::Grow => write!(f, "grow"),
+            Overflow::Wrap => write!(f, "wrap"),
+            Overflow::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// how big the tape is and what happens at its edges, cells are numbered from
+/// the origin, which is where the data pointer starts, with cells to its left
+/// being negative
+///
+/// the default policy is a tape that grows forever in both directions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TapePolicy {
+    /// the maximum amount of cells on the tape, when the tape has a fixed size
+    /// and this is not set, `DEFAULT_TAPE_LEN` is used instead
+    ///
+    /// a tape always has at least one cell, the one the data pointer starts at
+    pub max_len: Option<NonZeroUsize>,
+    /// whether there are cells to the left of the origin, when there aren't,
+    /// moving left of the origin is always an error
+    pub allow_left: bool,
+    /// what happens when moving past the end of the tape
+    pub overflow: Overflow,
+}
+
+impl Default for TapePolicy {
+    fn default() -> TapePolicy {
+        TapePolicy {
+            max_len: None,
+            allow_left: true,
+            overflow: Overflow::default(),
+        }
+    }
+}
+
+impl TapePolicy {
+    /// the amount of cells on a tape of fixed size
+    pub fn fixed_len(&self) -> usize {
+        self.max_len.map_or(DEFAULT_TAPE_LEN, NonZeroUsize::get)
+    }
+
+    /// where the origin is on a tape of fixed size, wrapping tapes and tapes
+    /// with nothing to the left start at their first cell, and the others start
+    /// in the middle so there is room on both sides
+    ///
+    /// growing tapes only have a fixed size once compiled, which is why they
+    /// get an origin too
+    ///
+    /// ```rust
+    /// use parser::tape::{Overflow, TapePolicy};
+    /// use std::num::NonZeroUsize;
+    ///
+    /// let policy = TapePolicy {
+    ///     max_len: NonZeroUsize::new(100),
+    ///     allow_left: true,
+    ///     overflow: Overflow::Error,
+    /// };
+    /// assert_eq!(policy.origin(), 50);
+    /// assert_eq!(TapePolicy { allow_left: false, ..policy }.origin(), 0);
+    /// assert_eq!(TapePolicy { overflow: Overflow::Grow, ..policy }.origin(), 50);
+    /// assert_eq!(TapePolicy { overflow: Overflow::Wrap, ..policy }.origin(), 0);
+    /// ```
+    pub fn origin(&self) -> usize {
+        match self.overflow {
+            Overflow::Grow | Overflow::Error if self.allow_left => self.fixed_len() / 2,
+            _ => 0,
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use crate::history::History;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// what the REPL should do after the editor handles a key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// the line changed, or didn't, but it is still being edited
+    Edit,
+    /// the line was submitted with Enter
+    Submit(String),
+    /// the line was thrown away with Ctrl-C
+    Cancel,
+    /// Ctrl-D was pressed on an empty line
+    Exit,
+}
+
+/// the line being typed into the REPL, it knows nothing about the terminal,
+/// it only turns keys into changes to the line, which the REPL then draws
+///
+/// the cursor counts characters, not bytes, so it can be used as a column
+#[derive(Debug, Default)]
+pub struct Editor {
+    line: Vec<char>,
+    cursor: usize,
+    /// which entry of the history is being shown, `None` when editing a new
+    /// line
+    browsing: Option<usize>,
+    /// the new line being edited before browsing the history, so going past
+    /// the most recent entry brings it back
+    draft: Vec<char>,
+}
+
+impl Editor {
+    pub fn new() -> Editor {
+        Editor::default()
+    }
+
+    pub fn line(&self) -> String {
+        self.line.iter().collect()
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    fn clear(&mut self) {
+        self.line.clear();
+        self.draft.clear();
+        self.cursor = 0;
+        self.browsing = None;
+    }
+
+    fn show(&mut self, line: Vec<char>) {
+        self.cursor = line.len();
+        self.line = line;
+    }
+
+    fn older(&mut self, history: &History) {
+        let index = match self.browsing {
+            Some(0) => return,
+            Some(index) => index - 1,
+            None if history.entries().is_empty() => return,
+            None => {
+                self.draft = std::mem::take(&mut self.line);
+                history.entries().len() - 1
+            }
+        };
+        self.browsing = Some(index);
+        self.show(history.entries()[index].chars().collect());
+    }
+
+    fn newer(&mut self, history: &History) {
+        let Some(index) = self.browsing else {
+            return;
+        };
+        match history.entries().get(index + 1) {
+            Some(entry) => {
+                self.browsing = Some(index + 1);
+                self.show(entry.chars().collect());
+            }
+            None => {
+                self.browsing = None;
+                let draft = std::mem::take(&mut self.draft);
+                self.show(draft);
+            }
+        }
+    }
+
+    /// applies `key` to the line, `history` is only read from, adding the
+    /// submitted lines to it is up to the caller
+    pub fn handle(&mut self, key: KeyEvent, history: &History) -> Action {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Char('c') if control => {
+                self.clear();
+                return Action::Cancel;
+            }
+            KeyCode::Char('d') if control && self.line.is_empty() => return Action::Exit,
+            KeyCode::Char('d') if control => _ = self.handle(KeyCode::Delete.into(), history),
+            KeyCode::Char('a') if control => self.cursor = 0,
+            KeyCode::Char('e') if control => self.cursor = self.line.len(),
+            // other chords would otherwise type their letter into the line
+            _ if control => {}
+            KeyCode::Char(c) => {
+                self.line.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.line.remove(self.cursor);
+            }
+            KeyCode::Delete if self.cursor < self.line.len() => {
+                self.line.remove(self.cursor);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.line.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.line.len(),
+            KeyCode::Up => self.older(history),
+            KeyCode::Down => self.newer(history),
+            KeyCode::Enter => {
+                let line = self.line();
+                self.clear();
+                return Action::Submit(line);
+            }
+            _ => {}
+        }
+
+        Action::Edit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typing(editor: &mut Editor, text: &str, history: &History) {
+        for c in text.chars() {
+            editor.handle(KeyCode::Char(c).into(), history);
+        }
+    }
+
+    #[test]
+    fn editing() {
+        let history = History::new();
+        let mut editor = Editor::new();
+        typing(&mut editor, "+++", &history);
+        editor.handle(KeyCode::Left.into(), &history);
+        editor.handle(KeyCode::Backspace.into(), &history);
+        typing(&mut editor, ">", &history);
+        assert_eq!(editor.line(), "+>+");
+        assert_eq!(editor.cursor(), 2);
+
+        editor.handle(KeyCode::Home.into(), &history);
+        editor.handle(KeyCode::Delete.into(), &history);
+        editor.handle(KeyCode::End.into(), &history);
+        typing(&mut editor, ".", &history);
+        assert_eq!(
+            editor.handle(KeyCode::Enter.into(), &history),
+            Action::Submit(">+.".to_string())
+        );
+        assert_eq!(editor.line(), "");
+
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        typing(&mut editor, "+", &history);
+        assert_eq!(editor.handle(ctrl('d'), &history), Action::Edit);
+        assert_eq!(editor.handle(ctrl('l'), &history), Action::Edit);
+        assert_eq!(editor.line(), "+");
+        assert_eq!(editor.handle(ctrl('c'), &history), Action::Cancel);
+        assert_eq!(editor.line(), "");
+        assert_eq!(editor.handle(ctrl('d'), &history), Action::Exit);
+    }
+
+    #[test]
+    fn browsing_history() {
+        let mut history = History::new();
+        history.push("first").unwrap();
+        history.push("second").unwrap();
+
+        let mut editor = Editor::new();
+        typing(&mut editor, "draft", &history);
+        editor.handle(KeyCode::Up.into(), &history);
+        assert_eq!(editor.line(), "second");
+        editor.handle(KeyCode::Up.into(), &history);
+        editor.handle(KeyCode::Up.into(), &history);
+        assert_eq!(editor.line(), "first");
+        assert_eq!(editor.cursor(), 5);
+
+        editor.handle(KeyCode::Down.into(), &history);
+        assert_eq!(editor.line(), "second");
+        editor.handle(KeyCode::Down.into(), &history);
+        assert_eq!(editor.line(), "draft");
+    }
+}
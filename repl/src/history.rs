@@ -0,0 +1,86 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+/// every line submitted to the REPL, oldest first, when the history has a
+/// file, every new entry is appended to it right away, so it survives the REPL
+/// being closed in any way
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /// a history that is only kept in memory
+    pub fn new() -> History {
+        History::default()
+    }
+
+    /// where the history is kept by default, inside of the data directory of
+    /// the user, if they have one
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("bfck").join("history"))
+    }
+
+    /// reads the history from the file at `path`, a file that doesn't exist
+    /// or can't be read is the same as an empty history
+    pub fn load(path: PathBuf) -> History {
+        let entries = std::fs::read_to_string(&path)
+            .map(|contents| contents.lines().map(String::from).collect())
+            .unwrap_or_default();
+
+        History {
+            entries,
+            path: Some(path),
+        }
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// adds `entry` to the end of the history, blank entries and entries equal
+    /// to the last one are skipped
+    pub fn push(&mut self, entry: &str) -> std::io::Result<()> {
+        if entry.trim().is_empty() || self.entries.last().is_some_and(|last| last == entry) {
+            return Ok(());
+        }
+        self.entries.push(entry.to_string());
+
+        let Some(path) = self.path.as_ref() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        writeln!(file, "{}", entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_are_persisted() {
+        let path = std::env::temp_dir()
+            .join(format!("bfck-history-{}", std::process::id()))
+            .join("history");
+        _ = std::fs::remove_file(&path);
+
+        let mut history = History::load(path.clone());
+        history.push("+++").unwrap();
+        history.push("+++").unwrap();
+        history.push("  ").unwrap();
+        history.push(".").unwrap();
+        assert_eq!(history.entries(), ["+++", "."]);
+
+        let history = History::load(path.clone());
+        assert_eq!(history.entries(), ["+++", "."]);
+        _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -1,11 +1,94 @@
 use crossterm::{
     cursor,
-    event::{self, Event, KeyCode, KeyEvent},
-    execute, terminal,
+    event::{self, Event, KeyEventKind},
+    execute, queue, terminal,
 };
 use std::io::Write;
 
-pub fn run() {
+pub mod editor;
+pub mod history;
+pub mod session;
+
+use editor::{Action, Editor};
+use history::History;
+use session::Session;
+
+/// shown before every line
+static PROMPT: &str = "> ";
+/// shown before lines that continue loops left open by previous ones
+static CONTINUATION_PROMPT: &str = ". ";
+
+/// keeps the terminal in raw mode for as long as it lives, restoring it when
+/// dropped, so the terminal is usable again even when we bail out early
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> std::io::Result<RawMode> {
+        terminal::enable_raw_mode()?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        _ = terminal::disable_raw_mode();
+    }
+}
+
+/// draws the line being edited over the current line of the terminal
+fn draw<W>(stdout: &mut W, prompt: &str, editor: &Editor) -> std::io::Result<()>
+where
+    W: Write,
+{
+    let column = prompt.chars().count() + editor.cursor();
+    queue!(
+        stdout,
+        cursor::MoveToColumn(0),
+        terminal::Clear(terminal::ClearType::CurrentLine)
+    )?;
+    write!(stdout, "{}{}", prompt, editor.line())?;
+    queue!(stdout, cursor::MoveToColumn(column as u16))?;
+    stdout.flush()
+}
+
+/// lets the user edit a single line, the terminal is only in raw mode while
+/// the line is being edited, so programs run with the terminal as they expect
+fn read_line<W>(
+    stdout: &mut W,
+    prompt: &str,
+    editor: &mut Editor,
+    history: &History,
+) -> std::io::Result<Action>
+where
+    W: Write,
+{
+    let _raw_mode = RawMode::enable()?;
+    draw(stdout, prompt, editor)?;
+
+    loop {
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        let action = editor.handle(key, history);
+        if action == Action::Cancel {
+            write!(stdout, "^C")?;
+        }
+        if action != Action::Edit {
+            write!(stdout, "\r\n")?;
+            stdout.flush()?;
+            return Ok(action);
+        }
+        draw(stdout, prompt, editor)?;
+    }
+}
+
+/// runs the REPL on the real terminal until the user exits, every line runs on
+/// `interpreter`, and is parsed with `extensions`
+pub fn run(interpreter: interpreter::Interpreter, extensions: lexer::Extensions) {
     let mut stdout = std::io::stdout();
     execute!(
         stdout,
@@ -14,34 +97,45 @@ pub fn run() {
     )
     .expect("failed to flush stdout");
 
-    let mut events = vec![];
+    let mut editor = Editor::new();
+    let mut history = History::default_path()
+        .map(History::load)
+        .unwrap_or_default();
+    let mut session =
+        Session::new(std::io::stdout(), std::io::stdin(), interpreter).with_extensions(extensions);
 
     loop {
-        stdout.flush().expect("failed to flush stdout");
-        let event = event::read().expect("failed to read stdin");
-        events.push(event.clone());
-        if let Event::Key(KeyEvent {
-            code: KeyCode::Enter,
-            ..
-        }) = event
+        let prompt = match session.is_pending() {
+            true => CONTINUATION_PROMPT,
+            false => PROMPT,
+        };
+
+        let code = match read_line(&mut stdout, prompt, &mut editor, &history)
+            .expect("failed to read stdin")
         {
-            let code = events.iter().fold(String::default(), |mut acc, event| {
-                if let Event::Key(KeyEvent {
-                    code: KeyCode::Char(c),
-                    ..
-                }) = event
-                {
-                    acc.push(*c);
-                };
-                acc
-            });
-
-            let tokens = lexer::Lexer::tokenize(&code);
-            let interpreter = interpreter::Interpreter::default();
-            let mut parser = parser::Parser::new(std::io::stdout(), std::io::stdin(), interpreter);
-            parser.interpret(tokens);
-            events.clear();
-            execute!(stdout, cursor::MoveToNextLine(1)).expect("failed to write to stdout");
+            Action::Submit(code) => code,
+            Action::Cancel => {
+                session.cancel();
+                continue;
+            }
+            Action::Exit => break,
+            Action::Edit => unreachable!(),
+        };
+
+        if let Err(e) = history.push(&code) {
+            writeln!(stdout, "failed to save history: {}", e).expect("failed to write to stdout");
+        }
+
+        match session.eval(&code) {
+            // programs don't always end their output with a new line, so we
+            // make sure the next prompt starts on a line of its own
+            Ok(reply) if reply.is_empty() => {
+                if !code.trim().is_empty() && !session.is_pending() {
+                    writeln!(stdout).expect("failed to write to stdout");
+                }
+            }
+            Ok(reply) => writeln!(stdout, "{}", reply).expect("failed to write to stdout"),
+            Err(e) => writeln!(stdout, "error: {}", e).expect("failed to write to stdout"),
         }
     }
 }
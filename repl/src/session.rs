@@ -0,0 +1,351 @@
+use interpreter::Interpreter;
+use lexer::Extensions;
+use parser::error::{Error, ParseError};
+use parser::program::Program;
+use parser::Parser;
+
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+
+/// how many cells to each side of the data pointer `:tape` shows
+static TAPE_RADIUS: usize = 8;
+
+static HELP: &str = "\
+anything that doesn't start with `:` is run as brainfuck code, lines with
+loops that are still open continue on the next line
+
+commands:
+  :load <file>         run the code in file
+  :save <file>         write every line of code run so far to file
+  :tape [start end]    show the cells around the data pointer, or the cells
+                       from start to end, the current cell is between brackets
+  :ptr                 show the cell the data pointer is at
+  :input <text>        queue text to be read by the program before stdin,
+                       \\n, \\t and \\\\ are a new line, a tab and a backslash
+  :reset               clear the tape and forget the code run so far
+  :help                show this message";
+
+/// the input of the programs run by a session, bytes queued through `:input`
+/// are read before anything from the underlying reader
+#[derive(Debug)]
+pub struct QueuedInput<R>
+where
+    R: Read,
+{
+    queued: VecDeque<u8>,
+    reader: R,
+}
+
+impl<R> Read for QueuedInput<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.queued.is_empty() {
+            true => self.reader.read(buf),
+            false => self.queued.read(buf),
+        }
+    }
+}
+
+/// replaces the escape sequences `:input` understands with what they stand for,
+/// unknown sequences are kept as they are
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// a single interpreter that lives for as long as the REPL does, so the tape
+/// and the data pointer carry over from one line to the next
+#[derive(Debug)]
+pub struct Session<W, R>
+where
+    W: Write,
+    R: Read,
+{
+    parser: Parser<W, QueuedInput<R>, Interpreter>,
+    /// code from previous lines that opened loops which were not closed yet,
+    /// it only runs once every loop is closed
+    pending: String,
+    /// every piece of code that ran, in order, which is what `:save` writes
+    transcript: Vec<String>,
+    /// the commands besides the standard ones that code typed or loaded into
+    /// the session understands
+    extensions: Extensions,
+}
+
+impl<W, R> Session<W, R>
+where
+    W: Write,
+    R: Read,
+{
+    pub fn new(writer: W, reader: R, interpreter: Interpreter) -> Session<W, R> {
+        let input = QueuedInput {
+            queued: VecDeque::new(),
+            reader,
+        };
+        Session {
+            parser: Parser::new(writer, input, interpreter),
+            pending: String::new(),
+            transcript: vec![],
+            extensions: Extensions::default(),
+        }
+    }
+
+    pub fn with_extensions(mut self, extensions: Extensions) -> Session<W, R> {
+        self.extensions = extensions;
+        self
+    }
+
+    pub fn interpreter(&self) -> &Interpreter {
+        self.parser.instruction_handler()
+    }
+
+    pub fn writer(&self) -> &W {
+        self.parser.writer()
+    }
+
+    /// whether there are loops left open by previous lines, in which case the
+    /// next line continues them instead of starting over
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// throws away the code of loops left open by previous lines
+    pub fn cancel(&mut self) {
+        self.pending.clear();
+    }
+
+    /// runs a line typed into the REPL, lines starting with `:` are commands
+    /// for the REPL itself, everything else is brainfuck code, returns what
+    /// should be shown to the user, besides the output of the program
+    ///
+    /// code with loops that are still open is kept around until a later line
+    /// closes them, and only then the whole thing runs, while that happens,
+    /// every line is code, even if it starts with `:`
+    pub fn eval(&mut self, line: &str) -> Result<String, Error> {
+        if !self.is_pending() {
+            if let Some(command) = line.trim_start().strip_prefix(':') {
+                return self.command(command.trim());
+            }
+        }
+
+        let code = match self.is_pending() {
+            true => format!("{}\n{}", std::mem::take(&mut self.pending), line),
+            false => line.to_string(),
+        };
+
+        match Program::parse_with(&code, self.extensions) {
+            Ok(_) => self.run(code).map(|_| String::new()),
+            Err(ParseError::UnmatchedOpen(_)) => {
+                self.pending = code;
+                Ok(String::new())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// runs `code` on the interpreter of the session, keeping it around in
+    /// the transcript
+    fn run(&mut self, code: String) -> Result<(), Error> {
+        let program = Program::parse_with(&code, self.extensions)?;
+        self.transcript.push(code);
+        let result = self.parser.interpret(program);
+        self.parser.writer_mut().flush()?;
+        result
+    }
+
+    fn command(&mut self, command: &str) -> Result<String, Error> {
+        let (name, argument) = command
+            .split_once(char::is_whitespace)
+            .map(|(name, argument)| (name, argument.trim()))
+            .unwrap_or((command, ""));
+
+        let reply = match (name, argument) {
+            ("reset", _) => {
+                self.parser.instruction_handler_mut().reset();
+                self.transcript.clear();
+                "tape cleared".to_string()
+            }
+            ("tape", "") => {
+                let position = self.interpreter().position();
+                let radius = TAPE_RADIUS as isize;
+                self.render_tape(position - radius, position + radius)
+            }
+            ("tape", range) => {
+                let bounds = range
+                    .split_whitespace()
+                    .map(str::parse::<isize>)
+                    .collect::<Result<Vec<_>, _>>();
+                match bounds.as_deref() {
+                    Ok([start, end]) if start <= end => self.render_tape(*start, *end),
+                    _ => "expected the first and last cells to show, like `:tape -4 4`".to_string(),
+                }
+            }
+            ("ptr", _) => {
+                let position = self.interpreter().position();
+                format!(
+                    "cell {}, holding {}",
+                    position,
+                    self.interpreter().peek(position)
+                )
+            }
+            ("load", "") | ("save", "") => format!("expected a file to {}", name),
+            ("load", path) => {
+                let code = std::fs::read_to_string(path)?;
+                self.run(code)?;
+                format!("loaded {}", path)
+            }
+            ("save", path) => {
+                let mut contents = self.transcript.join("\n");
+                contents.push('\n');
+                std::fs::write(path, contents)?;
+                format!("saved {} lines of code to {}", self.transcript.len(), path)
+            }
+            ("input", text) => {
+                let text = unescape(text);
+                self.parser.reader_mut().queued.extend(text.bytes());
+                format!("queued {} bytes of input", text.len())
+            }
+            ("help", _) => HELP.to_string(),
+            _ => format!("unknown command `:{}`, try `:help`", command),
+        };
+
+        Ok(reply)
+    }
+
+    /// the cells from `start` to `end`, with the current cell between brackets
+    fn render_tape(&self, start: isize, end: isize) -> String {
+        let position = self.interpreter().position();
+
+        let mut positions = String::new();
+        let mut values = String::new();
+        for cell in start..=end {
+            let value = self.interpreter().peek(cell);
+            positions.push_str(&format!("{:>6}", cell));
+            match cell == position {
+                true => values.push_str(&format!("{:>6}", format!("[{}]", value))),
+                false => values.push_str(&format!("{:>6}", value)),
+            }
+        }
+
+        format!("{}\n{}", positions, values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> Session<Vec<u8>, std::io::Empty> {
+        Session::new(vec![], std::io::empty(), Interpreter::new())
+    }
+
+    #[test]
+    fn state_is_kept_between_lines() {
+        let mut session = session();
+        session.eval("++++++++[>++++++++<-]").unwrap();
+        session.eval(">+").unwrap();
+        session.eval(".").unwrap();
+        assert_eq!(session.writer(), b"A");
+        assert_eq!(session.eval(":ptr").unwrap(), "cell 1, holding 65");
+    }
+
+    #[test]
+    fn open_loops_continue_on_the_next_line() {
+        let mut session = session();
+        session.eval("++++++++[>++++++++").unwrap();
+        assert!(session.is_pending());
+        // commands are code while loops are open
+        session.eval(":ptr <-").unwrap();
+        assert!(session.is_pending());
+        session.eval("]>+.").unwrap();
+        assert!(!session.is_pending());
+        assert_eq!(session.writer(), b"A");
+
+        // stray brackets are still errors, and are not kept around
+        assert!(session.eval("]").is_err());
+        assert!(!session.is_pending());
+    }
+
+    #[test]
+    fn commands() {
+        let mut session = session();
+        session.eval(">+++").unwrap();
+        let tape = session.eval(":tape").unwrap();
+        assert!(tape.contains("[3]"));
+
+        assert_eq!(session.eval(":reset").unwrap(), "tape cleared");
+        assert_eq!(session.eval(":ptr").unwrap(), "cell 0, holding 0");
+        assert_eq!(
+            session.eval(":nope").unwrap(),
+            "unknown command `:nope`, try `:help`"
+        );
+        assert!(session.eval(":help").unwrap().contains(":load <file>"));
+    }
+
+    #[test]
+    fn tape_ranges() {
+        let mut session = session();
+        session.eval("+>++>+++<").unwrap();
+        assert_eq!(
+            session.eval(":tape -1 2").unwrap(),
+            "    -1     0     1     2\n     0     1   [2]     3"
+        );
+        assert!(session.eval(":tape 2 -1").unwrap().starts_with("expected"));
+    }
+
+    #[test]
+    fn queued_input() {
+        let mut session = session();
+        assert_eq!(
+            session.eval(":input a\\nb").unwrap(),
+            "queued 3 bytes of input"
+        );
+        session.eval(",.,.,.").unwrap();
+        assert_eq!(session.writer(), b"a\nb");
+    }
+
+    #[test]
+    fn loading_and_saving() {
+        let dir = std::env::temp_dir().join(format!("bfck-session-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("source.bf");
+        let saved = dir.join("saved.bf");
+        std::fs::write(&source, "++++++++[>++++++++<-]").unwrap();
+
+        let mut session = session();
+        let load = format!(":load {}", source.display());
+        assert!(session.eval(&load).unwrap().starts_with("loaded"));
+        session.eval(">+.[").unwrap();
+        session.eval("-]").unwrap();
+        assert_eq!(session.writer(), b"A");
+
+        let save = format!(":save {}", saved.display());
+        assert!(session.eval(&save).unwrap().starts_with("saved 2 lines"));
+        assert_eq!(
+            std::fs::read_to_string(&saved).unwrap(),
+            "++++++++[>++++++++<-]\n>+.[\n-]\n"
+        );
+
+        assert!(session.eval(":load nowhere.bf").is_err());
+        _ = std::fs::remove_dir_all(dir);
+    }
+}